@@ -1,84 +1,71 @@
-/**
+/*!
  * Author: Sullivan Lucas Myer
  * -----------------------------------------
  * Decryption client for one-time pad encryption.
- * Reads ciphertext and key from respective files,
+ * Reads ciphertext and key from respective files
+ * (either may be `-` for stdin),
  * interleaves ciphertext and key characters,
  * and sends interleaved buffer to dec_server.
  * Receives decrypted text back from dec_server,
  * and writes it to stdout.
+ * With --offline, runs the cipher in-process instead.
+ * With --no-newline, text output isn't ended with a newline.
  * Implements an application-level handshake protocol,
  * which prevents connection to enc_server.
+ * Thin wrapper around the shared client in utils;
+ * see the otp CLI for the flag-based interface.
  * -----------------------------------------
  */
 
 /*-----------USE STATEMENTS-----------*/
-use std::env::args;
-use std::net::TcpStream;
+use std::io;
 use std::process::exit;
-use utils::{
-    read_file,
-    validate_buffer,
-    interleave_buffers,
-    client_handshake,
-    send_and_receive
-};
+use utils::{run_client, Args, ClientRequest, Direction, Mode, OtpError, Timeouts};
 /*-----------USE STATEMENTS-----------*/
 
-/*-----------CONSTANT DEFINITIONS-----------*/
-const HOSTNAME: &str = "0.0.0.0";
-const HANDSHAKE_SIGNAL: &str = "@";
-const TERMINATION_SIGNAL: &str = "@";
-/*-----------CONSTANT DEFINITIONS-----------*/
- 
 /*-----------MAIN-----------*/
- fn main() {
+fn main() {
     /*-----------CHECK ARGS-----------*/
-    let args: Vec<String> = args().collect();
-    if args.len() < 4 {
-        eprintln!("USAGE: {} ciphertext_file key_file port", args[0]);
-        exit(1);
+    let mut args = Args::from_env();
+    let program = args.program().to_string();
+    let usage = |e: OtpError| -> ! {
+        eprintln!("Error: {}", e);
+        eprintln!("USAGE: {} ciphertext_file key_file port [mode] [--host HOST] [--no-newline]", program);
+        eprintln!("       {} ciphertext_file key_file [mode] --offline", program);
+        eprintln!("       either file may be - to read it from stdin");
+        eprintln!("       timeouts: [--handshake-timeout SECONDS] [--idle-timeout SECONDS] [--session-timeout SECONDS]");
+        exit(e.exit_code());
+    };
+    let host = args.value("--host").unwrap_or_else(|e| usage(e));
+    let timeouts = Timeouts::from_args(&mut args).unwrap_or_else(|e| usage(e));
+    let offline = args.switch("--offline");
+    let no_newline = args.switch("--no-newline");
+    let args = args.positionals().unwrap_or_else(|e| usage(e));
+    let port_count = if offline { 0 } else { 1 }; // no port when running offline
+    if args.len() < 2 + port_count {
+        usage(OtpError::Input("Missing arguments".to_string()));
     }
     /*-----------CHECK ARGS-----------*/
 
     /*-----------INITIALIZE-----------*/
-    let cipher = &args[1];
-    let key = &args[2];
-    let port = &args[3];
-    let address = format!("{}:{}", HOSTNAME, port);
+    let port = if offline { "" } else { &args[2] };
+    let mut request = ClientRequest::new(Direction::Decrypt, &args[0], &args[1], port);
+    request.offline = offline;
+    request.trailing_newline = !no_newline;
+    request.timeouts = timeouts;
+    if let Some(host) = host {
+        request.host = host;
+    }
+    if let Some(descriptor) = args.get(2 + port_count) {
+        request.mode = descriptor.parse::<Mode>().unwrap_or_else(|e| usage(e));
+    }
     /*-----------INITIALIZE-----------*/
-    
-    /*-----------READ & VALIDATE INPUT-----------*/
-    let mut ct_buffer = read_file(&cipher).expect("Error reading ciphertext file");
-    let mut key_buffer = read_file(&key).expect("Error reading key file");
-
-    ct_buffer = ct_buffer.trim_end_matches('\n').to_string();
-    key_buffer = key_buffer.trim_end_matches('\n').to_string();
 
-    if key_buffer.len() < ct_buffer.len() {
-        eprintln!("Error: Key is too short");
-        exit(1);
+    /*-----------RUN SESSION-----------*/
+    if let Err(e) = run_client(&request, &mut io::stdout().lock()) {
+        eprintln!("{}", e);
+        exit(e.exit_code());
     }
-
-    validate_buffer(&ct_buffer).expect("Plaintext contains invalid characters");
-    validate_buffer(&key_buffer).expect("Key contains invalid characters");
-    /*-----------READ & VALIDATE INPUT-----------*/
-
-    /*-----------INTERLEAVE BUFFERS-----------*/
-    let mut interleaved_buffer = interleave_buffers(&ct_buffer, &key_buffer);
-    /*-----------INTERLEAVE BUFFERS-----------*/
-
-    /*-----------CONNECT TO SERVER-----------*/
-    let mut stream = TcpStream::connect(address).expect("Failed to connect to server");
-    /*-----------CONNECT TO SERVER-----------*/
-
-    /*-----------HANDSHAKE-----------*/
-    client_handshake(&mut stream, HANDSHAKE_SIGNAL).expect("Handshake failed");
-    /*-----------HANDSHAKE-----------*/
-    
-    /*-----------SEND & RECEIVE-----------*/
-    send_and_receive(&mut stream, &mut interleaved_buffer, TERMINATION_SIGNAL).expect("Communication error");
-    /*-----------SEND & RECEIVE-----------*/
-
+    /*-----------RUN SESSION-----------*/
 }
-/*-----------MAIN-----------*/
\ No newline at end of file
+/*-----------MAIN-----------*/
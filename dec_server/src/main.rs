@@ -1,4 +1,4 @@
-/**
+/*!
  * Author: Sullivan Lucas Myer
  * -----------------------------------------
  * Decryption server for one-time pad encryption.
@@ -7,44 +7,34 @@
  * sends the decrypted text back to dec_client.
  * Implements an application-level handshake protocol,
  * which prevents enc_client from connecting to dec_server.
+ * Thin wrapper around the shared server in utils,
+ * restricted to decryption; see otp_server for both.
  * -----------------------------------------
  */
 
 /*-----------USE STATEMENTS-----------*/
-use std::net::TcpListener;
 use std::process::exit;
-use std::thread::spawn;
-use std::env::args;
-use utils::handle_dec_client;
+use utils::{run_server, Args, Direction, ServerConfig};
 /*-----------USE STATEMENTS-----------*/
 
-/*-----------CONSTANT DEFINITIONS-----------*/
-const HOSTNAME: &str = "0.0.0.0";
-const HANDSHAKE_SIGNAL: char = '@';
-const TERMINATION_SIGNAL: char = '@';
-/*-----------CONSTANT DEFINITIONS-----------*/
-
 /*-----------MAIN-----------*/
 fn main() {
-    let args: Vec<String> = args().collect();
-    if args.len() != 2 {
-        eprintln!("USAGE: {} port", args[0]);
-        exit(1);
-    }
+    let args = Args::from_env();
+    let program = args.program().to_string();
+    let config = ServerConfig::from_args(args, &[Direction::Decrypt]).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        eprintln!("USAGE: {} port [mode] [--bind ADDRESS] [--pad-dir DIR] [--grace-period SECONDS]", program);
+        #[cfg(feature = "async")]
+        eprintln!("       {} ... [--max-sessions N]", program);
+        #[cfg(not(feature = "async"))]
+        eprintln!("       {} ... [--workers N] [--backlog N] [--queue-policy block|reject|drop-oldest] [--stats SECONDS]", program);
+        eprintln!("       timeouts: [--handshake-timeout SECONDS] [--idle-timeout SECONDS] [--session-timeout SECONDS]");
+        exit(e.exit_code());
+    });
 
-    let port = &args[1];
-    let listener = TcpListener::bind(format!("{}:{}", HOSTNAME, port)).expect("Failed to bind to port");
-    println!("Server listening on port {}", port);
-
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                spawn(move || handle_dec_client(stream, HANDSHAKE_SIGNAL, TERMINATION_SIGNAL));
-            }
-            Err(e) => {
-                eprintln!("Failed to accept connection: {}", e);
-            }
-        }
+    if let Err(e) = run_server(config) {
+        eprintln!("Server error: {}", e);
+        exit(e.exit_code());
     }
 }
-/*-----------MAIN-----------*/
\ No newline at end of file
+/*-----------MAIN-----------*/
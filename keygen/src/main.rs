@@ -1,26 +1,44 @@
-/**
+/*!
  * Author:  Sullivan Lucas Myer
  * -----------------------------------------
  * Generates key for one-time pad encryption.
- * Consists of a string of random capital letters
- * and spaces, with a newline character at the end.
+ * Symbols are drawn from the OS entropy source
+ * without bias (see utils::generate_key).
+ * In text modes, consists of a string of random symbols
+ * drawn from the chosen alphabet (capital letters and
+ * spaces by default), with a newline character at the end.
+ * In xor mode, consists of raw random bytes.
+ * With --container, the key is wrapped in a header
+ * carrying its ID, mode, length and checksum.
  * -----------------------------------------
  */
-use rand::Rng;
-use std::env::args;
+use std::io::{stdout, Write};
+use std::process::exit;
+use utils::{generate_key_file, Args, Mode, OtpError};
 
 fn main() {
-    let args: Vec<String> = args().collect();
-    let length: usize = args[1].parse().unwrap_or_else(|_| panic!("Invalid length"));
-    let mut rng = rand::thread_rng();
-    let key: String = (0..length)
-        .map(|_| {
-            if rng.gen_range(0..27) == 26 {
-                ' '
-            } else {
-                ((rng.gen_range(0..26) + 65) as u8) as char
-            }
-        })
-        .collect();
-    println!("{}", key);
-}
\ No newline at end of file
+    let mut args = Args::from_env();
+    let program = args.program().to_string();
+    let usage = |e: OtpError| -> ! {
+        eprintln!("Error: {}", e);
+        eprintln!("USAGE: {} key_length [mode] [--container]", program);
+        exit(e.exit_code());
+    };
+    let container = args.switch("--container");
+    let args = args.positionals().unwrap_or_else(|e| usage(e));
+    if args.is_empty() {
+        usage(OtpError::Input("Missing key length".to_string()));
+    }
+    let length: usize = args[0].parse().unwrap_or_else(|_| usage(OtpError::Input(format!("Invalid length '{}'", args[0]))));
+    let mode: Mode = match args.get(1) {
+        Some(descriptor) => descriptor.parse().unwrap_or_else(|e| usage(e)),
+        None => Mode::default(),
+    };
+    let written = generate_key_file(length, &mode, container).and_then(|key| {
+        stdout().write_all(&key).map_err(|e| OtpError::Io("Error writing key".to_string(), e))
+    });
+    if let Err(e) = written {
+        eprintln!("Error: {}", e);
+        exit(e.exit_code());
+    }
+}
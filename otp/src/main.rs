@@ -0,0 +1,175 @@
+/*!
+ * -----------------------------------------
+ * Command-line front end for one-time pad encryption.
+ * One binary with a subcommand per task, sharing the
+ * client and key generation code in utils, and the
+ * same flags everywhere:
+ *   otp encrypt --port P --input FILE --key FILE [--host H] [--output FILE] [--no-newline] [--mode M] [--no-ledger] [--envelope] [--batch]
+ *   otp decrypt --port P --input FILE --key FILE [--host H] [--output FILE] [--no-newline] [--mode M] [--batch]
+ *   (with --batch, --input and --output are directories and every file in the
+ *    input directory is encrypted or decrypted over one connection)
+ *   (or --pad-id ID [--offset N] instead of --key, to use a pad held by the server,
+ *    or --offline instead of --port, to run the cipher in-process;
+ *    both take --handshake-timeout, --idle-timeout and --session-timeout in seconds)
+ *   (--input and --key may be - to read from stdin)
+ *   otp keygen  --length N [--output FILE] [--mode M] [--container]
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::io::Write;
+use std::process::exit;
+use utils::{generate_key_file, open_output, run_batch, run_client, Args, ClientRequest, Direction, Mode, OtpError, Timeouts, DEFAULT_HOST};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const USAGE: &str = "\
+USAGE:
+    otp encrypt --port PORT --input FILE --key FILE [--host HOST] [--output FILE] [--no-newline] [--mode MODE] [--no-ledger] [--envelope] [--batch]
+    otp decrypt --port PORT --input FILE --key FILE [--host HOST] [--output FILE] [--no-newline] [--mode MODE] [--batch]
+    (encrypt and decrypt take --pad-id ID [--offset N] instead of --key to use a pad held by the server,
+     or --offline instead of --port to run the cipher in-process;
+     both take [--handshake-timeout SECONDS] [--idle-timeout SECONDS] [--session-timeout SECONDS];
+     --input or --key may be - to read it from stdin;
+     with --batch, --input and --output are directories and each file is its own message)
+    otp keygen --length N [--output FILE] [--mode MODE] [--container]
+    otp help";
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------MAIN-----------*/
+fn main() {
+    let mut args = Args::from_env();
+    let command = args.command();
+
+    let result = match command.as_deref() {
+        Some("encrypt") => cipher(args, Direction::Encrypt),
+        Some("decrypt") => cipher(args, Direction::Decrypt),
+        Some("keygen") => keygen(args),
+        Some("help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ if args.switch("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(OtpError::Input(format!("Unknown command '{}'", other))),
+        None => Err(OtpError::Input("Missing command".to_string())),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        eprintln!("{}", USAGE);
+        exit(e.exit_code());
+    }
+}
+/*-----------MAIN-----------*/
+
+// Takes a required option, or fails with a usage error naming it.
+fn required(args: &mut Args, name: &str) -> Result<String, OtpError> {
+    args.value(name)?.ok_or_else(|| OtpError::Input(format!("Missing required option {}", name)))
+}
+
+// Rejects anything left over after the subcommand's options are taken.
+fn finish(args: Args) -> Result<(), OtpError> {
+    match args.positionals()?.first() {
+        Some(extra) => Err(OtpError::Input(format!("Unexpected argument '{}'", extra))),
+        None => Ok(()),
+    }
+}
+
+fn cipher(mut args: Args, operation: Direction) -> Result<(), OtpError> {
+    /*-----------PARSE OPTIONS-----------*/
+    let offline = args.switch("--offline");
+    let port = if offline {
+        args.value("--port")?.unwrap_or_default() // unused offline
+    } else {
+        required(&mut args, "--port")?
+    };
+    let input = required(&mut args, "--input")?;
+    let pad_id = args.value("--pad-id")?;
+    let key = match pad_id {
+        Some(_) => String::new(), // the server holds the pad
+        None => required(&mut args, "--key")?,
+    };
+    let batch = args.switch("--batch");
+    let output = match batch {
+        true => Some(required(&mut args, "--output")?),
+        false => args.value("--output")?,
+    };
+    let no_newline = args.switch("--no-newline");
+    let mut request = ClientRequest::new(operation, &input, &key, &port);
+    request.offline = offline;
+    request.trailing_newline = !no_newline;
+    request.timeouts = Timeouts::from_args(&mut args)?;
+    if pad_id.is_some() {
+        request.offset = args.parsed::<u64>("--offset")?;
+        request.pad_id = pad_id;
+    }
+    request.host = args.value("--host")?.unwrap_or_else(|| DEFAULT_HOST.to_string());
+    request.mode = args.parsed::<Mode>("--mode")?.unwrap_or_default();
+    if operation == Direction::Encrypt {
+        args.switch("--ledger"); // the default now; still accepted
+        request.ledger = !args.switch("--no-ledger");
+        request.envelope = args.switch("--envelope");
+    }
+    finish(args)?;
+    /*-----------PARSE OPTIONS-----------*/
+
+    /*-----------RUN BATCH-----------*/
+    if let (true, Some(output)) = (batch, &output) {
+        let summary = run_batch(&request, &input, output).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(e.exit_code());
+        });
+        for (name, e) in &summary.failed {
+            eprintln!("{}: {}", name, e);
+        }
+        println!("Batch: {}", summary);
+        if let Some((_, e)) = summary.failed.first() {
+            exit(e.exit_code());
+        }
+        return Ok(());
+    }
+    /*-----------RUN BATCH-----------*/
+
+    /*-----------RUN SESSION-----------*/
+    let mut out = open_output(output.as_deref()).unwrap_or_else(|e| {
+        let e = OtpError::Io("Error opening output".to_string(), e);
+        eprintln!("{}", e);
+        exit(e.exit_code());
+    });
+    // a failed session drops `out` uncommitted, so no partial file is left behind
+    let result = run_client(&request, &mut out)
+        .and_then(|_| out.commit().map_err(|e| OtpError::Io("Error writing output".to_string(), e)));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        exit(e.exit_code());
+    }
+    /*-----------RUN SESSION-----------*/
+    Ok(())
+}
+
+fn keygen(mut args: Args) -> Result<(), OtpError> {
+    /*-----------PARSE OPTIONS-----------*/
+    let length = args.parsed::<usize>("--length")?.ok_or_else(|| OtpError::Input("Missing required option --length".to_string()))?;
+    let output = args.value("--output")?;
+    let mode = args.parsed::<Mode>("--mode")?.unwrap_or_default();
+    let container = args.switch("--container");
+    finish(args)?;
+    /*-----------PARSE OPTIONS-----------*/
+
+    /*-----------WRITE KEY-----------*/
+    let written = generate_key_file(length, &mode, container).and_then(|key| {
+        let mut out = open_output(output.as_deref())?;
+        out.write_all(&key)?;
+        Ok(out.commit()?)
+    });
+    if let Err(e) = written {
+        let e = e.context("Error writing key");
+        eprintln!("{}", e);
+        exit(e.exit_code());
+    }
+    /*-----------WRITE KEY-----------*/
+    Ok(())
+}
@@ -0,0 +1,45 @@
+/*!
+ * -----------------------------------------
+ * Combined server for one-time pad encryption and decryption.
+ * Receives interleaved data and key from a client,
+ * encrypts or decrypts according to the operation
+ * requested in the handshake, and sends the result back.
+ * `--only encrypt` or `--only decrypt` restricts the
+ * listener to a single operation, like enc_server/dec_server.
+ * `--pad-dir` serves pads held by the server, so clients
+ * only send data.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::process::exit;
+use utils::{run_server, Args, Direction, OtpError, ServerConfig};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------MAIN-----------*/
+fn main() {
+    let mut args = Args::from_env();
+    let program = args.program().to_string();
+    let usage = |e: OtpError| -> ! {
+        eprintln!("Error: {}", e);
+        eprintln!("USAGE: {} port [mode] [--only encrypt|decrypt] [--bind ADDRESS] [--pad-dir DIR] [--grace-period SECONDS]", program);
+        #[cfg(feature = "async")]
+        eprintln!("       {} ... [--max-sessions N]", program);
+        #[cfg(not(feature = "async"))]
+        eprintln!("       {} ... [--workers N] [--backlog N] [--queue-policy block|reject|drop-oldest] [--stats SECONDS]", program);
+        eprintln!("       timeouts: [--handshake-timeout SECONDS] [--idle-timeout SECONDS] [--session-timeout SECONDS]");
+        exit(e.exit_code());
+    };
+
+    let operations = match args.parsed::<Direction>("--only").unwrap_or_else(|e| usage(e)) {
+        Some(operation) => vec![operation],
+        None => vec![Direction::Encrypt, Direction::Decrypt],
+    };
+    let config = ServerConfig::from_args(args, &operations).unwrap_or_else(|e| usage(e));
+
+    if let Err(e) = run_server(config) {
+        eprintln!("Server error: {}", e);
+        exit(e.exit_code());
+    }
+}
+/*-----------MAIN-----------*/
@@ -109,10 +109,10 @@ echo "Generating keys using built-in keygen for remaining tests"
 
 echo
 echo "#-----------------------------------------"
-echo '#enc_client plaintext1 key20 $encport'
+echo '#enc_client plaintext1 key20 $encport --no-ledger'
 tput bold; tput setaf 4; echo -n "#10 POINTS:"; tput sgr0; echo " Should return error about too-short key"
 ((tot+=10))
-msg=$(limit "${BIN_PATH}"/enc_client plaintext1 key20 $encport 3>&1 1>&2 2>&3 3>&-)
+msg=$(limit "${BIN_PATH}"/enc_client plaintext1 key20 $encport --no-ledger 3>&1 1>&2 2>&3 3>&-)
 printf 'stderr: %s\n' "$msg"
 if [ ! -z "$msg" ]
 then
@@ -123,13 +123,13 @@ else
 fi
 echo
 echo "#-----------------------------------------"
-echo '#enc_client plaintext1 key70000 $encport'
+echo '#enc_client plaintext1 key70000 $encport --no-ledger'
 tput bold; tput setaf 4; echo -n "#20 POINTS:"; tput sgr0; echo " Should return encrypted version of plaintext1"
 ((tot+=20))
-limit "${BIN_PATH}"/enc_client plaintext1 key70000 $encport | tee ciphertext1 | paste 
+limit "${BIN_PATH}"/enc_client plaintext1 key70000 $encport --no-ledger | tee ciphertext1 | paste 
 echo
 echo '#-----------------------------------------'
-echo '#enc_client plaintext1 key70000 $encport > ciphertext1'
+echo '#enc_client plaintext1 key70000 $encport --no-ledger > ciphertext1'
 tput bold; tput setaf 4; echo -n "#10 POINTS:"; tput sgr0; echo " ciphertext1 must exist"
 ((tot+=10))
 if [ -s ciphertext1 ]; then echo 'ciphertext1 exists!'; ((pts+=10)); else echo 'ciphertext1 DOES NOT EXIST'; fi 
@@ -233,11 +233,11 @@ limit() {
   head -c500 < STDERR\$suffix >&2 &
   "\$@" 1>STDOUT\$suffix 2>STDERR\$suffix
 }
-limit 1 "${BIN_PATH}"/enc_client plaintext1 key70000 $encport > ciphertext1 2>&3 &
-limit 2 "${BIN_PATH}"/enc_client plaintext2 key70000 $encport > ciphertext2 2>&3 &
-limit 3 "${BIN_PATH}"/enc_client plaintext3 key70000 $encport > ciphertext3 2>&3 &
-limit 4 "${BIN_PATH}"/enc_client plaintext4 key70000 $encport > ciphertext4 2>&3 &
-limit 5 "${BIN_PATH}"/enc_client plaintext5 key70000 $encport > ciphertext5 &
+limit 1 "${BIN_PATH}"/enc_client plaintext1 key70000 $encport --no-ledger > ciphertext1 2>&3 &
+limit 2 "${BIN_PATH}"/enc_client plaintext2 key70000 $encport --no-ledger > ciphertext2 2>&3 &
+limit 3 "${BIN_PATH}"/enc_client plaintext3 key70000 $encport --no-ledger > ciphertext3 2>&3 &
+limit 4 "${BIN_PATH}"/enc_client plaintext4 key70000 $encport --no-ledger > ciphertext4 2>&3 &
+limit 5 "${BIN_PATH}"/enc_client plaintext5 key70000 $encport --no-ledger > ciphertext5 &
 wait
 __CMDS__
 ret=$?
@@ -345,4 +345,36 @@ else
         echo "Files don't match :("
   ls -la plaintext* ciphertext*
         tput sgr0
-fi
\ No newline at end of file
+fi
+echo
+echo '#-----------------------------------------'
+echo '#enc_client ledgermsg keyledger $encport, twice, then dec_client on both'
+echo 'HELLO WORLD' > ledgermsg
+limit "${BIN_PATH}"/keygen 30 > keyledger
+limit "${BIN_PATH}"/enc_client ledgermsg keyledger $encport > ledgered1
+limit "${BIN_PATH}"/enc_client ledgermsg keyledger $encport > ledgered2
+limit "${BIN_PATH}"/dec_client ledgered1 keyledger $decport > ledgermsg1_a
+limit "${BIN_PATH}"/dec_client ledgered2 keyledger $decport > ledgermsg2_a
+tput bold; tput setaf 4; echo -n "#10 POINTS:"; tput sgr0; echo " The two messages should use different pad ranges, and both should decrypt"
+((tot+=10))
+if [ -s ledgered1 ] && ! cmp -s ledgered1 ledgered2 && cmp ledgermsg{,1_a} && cmp ledgermsg{,2_a}
+then
+        echo "Looks good!"
+        ((pts += 10))
+else
+        echo "Looks not good :("
+fi
+echo
+echo '#-----------------------------------------'
+echo '#enc_client ledgermsg keyledger $encport, once more than keyledger has room for'
+limit "${BIN_PATH}"/enc_client ledgermsg keyledger $encport > /dev/null 2>&1
+ret=$?
+tput bold; tput setaf 4; echo -n "#5 POINTS:"; tput sgr0; echo " Should fail with exit code 5 (key too short): $ret"
+((tot+=5))
+if [ $ret -eq 5 ]
+then
+        echo "Looks good!"
+        ((pts += 5))
+else
+        echo "Looks not good :("
+fi
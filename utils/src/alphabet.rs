@@ -0,0 +1,177 @@
+/*!
+ * -----------------------------------------
+ * Symbol sets for modular one-time pad arithmetic.
+ * An alphabet maps each symbol to its index and back,
+ * and is identified on the wire by a short descriptor
+ * ("classic", "printable", "alnum" or "custom:<symbols>").
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt;
+use std::str::FromStr;
+use crate::error::OtpError;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const CLASSIC_SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ ";
+const ALNUM_SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const CUSTOM_PREFIX: &str = "custom:";
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Classic,
+    Printable,
+    Alphanumeric,
+    Custom,
+}
+
+#[derive(Clone)]
+pub struct Alphabet {
+    kind: Kind,
+    symbols: Vec<u8>,
+    index: Box<[Option<u8>; 256]>, // symbol byte -> position in `symbols`
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl Alphabet {
+    /// The original 27-symbol set: A-Z followed by space.
+    pub fn classic() -> Self {
+        Self::build(Kind::Classic, CLASSIC_SYMBOLS.to_vec())
+    }
+
+    /// Every printable ASCII character, space (0x20) through tilde (0x7E).
+    pub fn printable() -> Self {
+        Self::build(Kind::Printable, (b' '..=b'~').collect())
+    }
+
+    /// Upper and lower case letters followed by the ten digits.
+    pub fn alphanumeric() -> Self {
+        Self::build(Kind::Alphanumeric, ALNUM_SYMBOLS.to_vec())
+    }
+
+    /// A caller-supplied symbol list, in the order given.
+    /// Symbols must be distinct printable ASCII characters, and there must be at least two.
+    pub fn custom(symbols: &str) -> Result<Self, OtpError> {
+        let symbols = symbols.as_bytes();
+        if symbols.len() < 2 {
+            return Err(OtpError::Input("Custom alphabet needs at least two symbols".to_string()));
+        }
+        let mut seen = [false; 256];
+        for &b in symbols {
+            if !(b' '..=b'~').contains(&b) {
+                return Err(OtpError::Input(format!("Custom alphabet symbol (byte: {}) is not printable ASCII", b)));
+            }
+            if seen[b as usize] {
+                return Err(OtpError::Input(format!("Custom alphabet repeats symbol '{}'", b as char)));
+            }
+            seen[b as usize] = true;
+        }
+        Ok(Self::build(Kind::Custom, symbols.to_vec()))
+    }
+
+    fn build(kind: Kind, symbols: Vec<u8>) -> Self {
+        let mut index = Box::new([None; 256]);
+        for (i, &b) in symbols.iter().enumerate() {
+            index[b as usize] = Some(i as u8);
+        }
+        Alphabet { kind, symbols, index }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn symbols(&self) -> &[u8] {
+        &self.symbols
+    }
+
+    pub fn contains(&self, symbol: u8) -> bool {
+        self.index[symbol as usize].is_some()
+    }
+
+    /// Position of `symbol` in the alphabet, if it belongs to it.
+    pub fn index_of(&self, symbol: u8) -> Option<usize> {
+        self.index[symbol as usize].map(usize::from)
+    }
+
+    /// Symbol at `index`, if the index is in range.
+    pub fn symbol_at(&self, index: usize) -> Option<u8> {
+        self.symbols.get(index).copied()
+    }
+
+    /// Checks every byte of `buffer`, reporting the first one outside the alphabet.
+    pub fn validate(&self, buffer: &[u8]) -> Result<(), OtpError> {
+        self.validate_at(buffer, 0)
+    }
+
+    /// Like `validate`, for a buffer that starts at position `start` of a longer stream.
+    pub fn validate_at(&self, buffer: &[u8], start: u64) -> Result<(), OtpError> {
+        match buffer.iter().position(|&b| !self.contains(b)) {
+            Some(i) => {
+                let position = start + i as u64;
+                let message = format!("Invalid character '{}' (byte: {}) at position {}", buffer[i] as char, buffer[i], position);
+                Err(OtpError::InvalidSymbol { position, message })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Wire descriptor, parseable back with `str::parse`.
+    pub fn descriptor(&self) -> String {
+        match self.kind {
+            Kind::Classic => "classic".to_string(),
+            Kind::Printable => "printable".to_string(),
+            Kind::Alphanumeric => "alnum".to_string(),
+            Kind::Custom => format!("{}{}", CUSTOM_PREFIX, String::from_utf8_lossy(&self.symbols)),
+        }
+    }
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl PartialEq for Alphabet {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbols == other.symbols
+    }
+}
+
+impl Eq for Alphabet {}
+
+impl fmt::Debug for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Alphabet").field(&self.descriptor()).finish()
+    }
+}
+
+impl fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.descriptor())
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = OtpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Self::classic()),
+            "printable" => Ok(Self::printable()),
+            "alnum" => Ok(Self::alphanumeric()),
+            _ => match s.strip_prefix(CUSTOM_PREFIX) {
+                Some(symbols) => Self::custom(symbols),
+                None => Err(OtpError::Input(format!("Unknown alphabet '{}'", s))),
+            },
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/*!
+ * -----------------------------------------
+ * Minimal command-line parsing shared by the binaries.
+ * Options are taken out by name (`--name value`,
+ * `--name=value` or a bare `--switch`), and whatever
+ * is left over becomes the positional arguments.
+ * A lone `-` is positional, and `--` ends option parsing.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use crate::error::OtpError;
+/*-----------USE STATEMENTS-----------*/
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    program: String,
+    tokens: Vec<String>,
+}
+
+impl Args {
+    pub fn from_env() -> Self {
+        Self::new(env::args().collect())
+    }
+
+    /// `raw[0]` is the program name, as with `std::env::args`.
+    pub fn new(raw: Vec<String>) -> Self {
+        let mut raw = raw.into_iter();
+        let program = raw.next().unwrap_or_default();
+        Args { program, tokens: raw.collect() }
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Removes and returns the first argument if it is not an option, as for a subcommand.
+    pub fn command(&mut self) -> Option<String> {
+        match self.tokens.first() {
+            Some(first) if !first.starts_with("--") => Some(self.tokens.remove(0)),
+            _ => None,
+        }
+    }
+
+    // Options may only appear before a `--` terminator.
+    fn option_tokens(&self) -> usize {
+        self.tokens.iter().position(|t| t == "--").unwrap_or(self.tokens.len())
+    }
+
+    /// Removes every occurrence of `--name` and reports whether there was one.
+    pub fn switch(&mut self, name: &str) -> bool {
+        let before = self.tokens.len();
+        let end = self.option_tokens();
+        let mut i = 0;
+        self.tokens.retain(|t| {
+            i += 1;
+            i > end || t != name
+        });
+        self.tokens.len() != before
+    }
+
+    /// Removes `--name value` or `--name=value` and returns the value.
+    /// When given more than once the last one wins.
+    pub fn value(&mut self, name: &str) -> Result<Option<String>, OtpError> {
+        let mut found = None;
+        let mut i = 0;
+        while i < self.option_tokens() {
+            if self.tokens[i] == name {
+                if i + 1 >= self.tokens.len() {
+                    return Err(OtpError::Input(format!("Option {} needs a value", name)));
+                }
+                self.tokens.remove(i);
+                found = Some(self.tokens.remove(i));
+            } else if let Some(value) = self.tokens[i].strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+                found = Some(value.to_string());
+                self.tokens.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Like `value`, parsing the value with `FromStr`.
+    pub fn parsed<T>(&mut self, name: &str) -> Result<Option<T>, OtpError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.value(name)? {
+            Some(value) => value.parse().map(Some).map_err(|e| OtpError::Input(format!("Invalid value for {}: {}", name, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// The remaining positional arguments. Any option that was never taken is an error.
+    pub fn positionals(self) -> Result<Vec<String>, OtpError> {
+        let end = self.option_tokens();
+        if let Some(unknown) = self.tokens[..end].iter().find(|t| t.starts_with("--")) {
+            return Err(OtpError::Input(format!("Unknown option {}", unknown)));
+        }
+        Ok(self.tokens.into_iter().enumerate().filter(|(i, _)| *i != end).map(|(_, t)| t).collect())
+    }
+}
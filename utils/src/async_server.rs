@@ -0,0 +1,201 @@
+/*!
+ * -----------------------------------------
+ * Async one-time pad server (cargo feature `async`).
+ * Serves each connection as a Tokio task instead of a
+ * thread, with at most `max_sessions` sessions at once.
+ * Clients beyond the limit get a `busy` ERROR frame and
+ * are disconnected rather than queued, so a burst of
+ * connections can't exhaust memory. Frames are handled
+ * by the same session code as the threaded server, under
+ * the same timeouts and graceful shutdown.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::future::Future;
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::time::{timeout, timeout_at};
+use crate::error::OtpError;
+use crate::frame::{encode_frame, parse_header, Frame, HEADER_LEN};
+use crate::net::resolve;
+use crate::server::{ServerConfig, DRAIN_TIMEOUT};
+use crate::server_error::{ErrorCode, ServerError};
+use crate::session::{Session, Step};
+use crate::shutdown::{DrainSummary, ShutdownFlag};
+use crate::timeout::{Phase, SessionClock};
+use crate::{server_error, CHUNK_SIZE};
+/*-----------USE STATEMENTS-----------*/
+
+/// Runs the async server on its own runtime until SIGTERM or SIGINT,
+/// then drains the active sessions and prints a summary.
+pub fn run_async_server(config: ServerConfig) -> Result<(), OtpError> {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()
+        .map_err(|e| OtpError::Io("Failed to start the async runtime".to_string(), e))?;
+    let result = runtime.block_on(serve(config));
+    runtime.shutdown_background(); // sessions still running after the grace period are aborted
+    result
+}
+
+async fn serve(config: ServerConfig) -> Result<(), OtpError> {
+    // only for exiting at once on a second signal
+    let _forced = ShutdownFlag::register().map_err(|e| OtpError::Io("Failed to install signal handlers".to_string(), e))?;
+    let stop = shutdown_signal();
+    tokio::pin!(stop);
+    let bind_failed = |e| OtpError::Connection(format!("Failed to bind {}:{}", config.bind, config.port), e);
+    let addresses = resolve(&config.bind, &config.port).map_err(bind_failed)?;
+    let listener = TcpListener::bind(addresses.as_slice()).await.map_err(bind_failed)?;
+    let local = listener.local_addr().map_err(bind_failed)?;
+    println!("Server listening on {} (async, at most {} sessions)", local, config.max_sessions);
+    let sessions = Arc::new(Semaphore::new(config.max_sessions));
+    let config = Arc::new(config);
+
+    loop {
+        let stream = tokio::select! {
+            _ = &mut stop => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+        };
+        match Arc::clone(&sessions).try_acquire_owned() {
+            Ok(permit) => {
+                let config = Arc::clone(&config);
+                tokio::spawn(async move {
+                    handle_client(stream, &config).await;
+                    drop(permit); // frees the slot for the next client
+                });
+            }
+            Err(_) => {
+                println!("Rejecting client: {} sessions already running", config.max_sessions);
+                let error = ServerError::new(ErrorCode::Busy, "Server is at its session limit, try again later");
+                tokio::spawn(reject(stream, error));
+            }
+        }
+    }
+    drop(listener);
+
+    /*-----------DRAIN SESSIONS-----------*/
+    let running = || config.max_sessions - sessions.available_permits();
+    let active = running();
+    println!("Shutting down: waiting up to {}s for {} active sessions", config.grace_period.as_secs(), active);
+    let _ = timeout(config.grace_period, sessions.acquire_many(config.max_sessions as u32)).await;
+    println!("Shutdown complete: {}", DrainSummary::new(active, running(), 0));
+    /*-----------DRAIN SESSIONS-----------*/
+    Ok(())
+}
+
+// Resolves on the first SIGTERM or SIGINT.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+// Serves one connection; errors carrying a `ServerError` are sent to the client.
+async fn handle_client(mut stream: TcpStream, config: &ServerConfig) {
+    if let Err(error) = serve_client(&mut stream, config).await {
+        let server_error = server_error(&error);
+        if server_error.is_some_and(|e| e.code == ErrorCode::Timeout) {
+            println!("Session timed out: {}", error);
+        } else {
+            println!("Session failed: {}", error);
+        }
+        if let Some(server_error) = server_error {
+            reject(stream, server_error.clone()).await;
+        }
+    }
+    println!("Client disconnected");
+}
+
+// Sends `error` and closes the connection once the client has hung up.
+async fn reject(mut stream: TcpStream, error: ServerError) {
+    let _ = write_frame(&mut stream, &error.to_frame()).await;
+    let _ = stream.shutdown().await;
+    // Unread client data would reset the connection and could discard the ERROR frame
+    let mut buffer = [0u8; CHUNK_SIZE];
+    while matches!(timeout(DRAIN_TIMEOUT, stream.read(&mut buffer)).await, Ok(Ok(n)) if n > 0) {}
+}
+
+// Runs the connection's sessions: one, or one per message in a batch.
+async fn serve_client(stream: &mut TcpStream, config: &ServerConfig) -> io::Result<()> {
+    let rejected = |error: ServerError| io::Error::new(io::ErrorKind::InvalidData, error);
+    let mut waiting = Phase::Handshake; // the first HELLO is part of the handshake
+
+    loop {
+        let clock = config.timeouts.start();
+
+        /*-----------HANDSHAKE-----------*/
+        let request = match timed(&clock, waiting, read_frame(stream)).await {
+            // a batch ends when the client hangs up instead of sending another HELLO
+            Err(e) if waiting == Phase::Idle && e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            request => request?,
+        };
+        // opening a server pad touches the disk, so keep it off the async workers
+        let (mut session, reply) = tokio::task::block_in_place(|| guarded(|| Session::start(config, &request))).map_err(rejected)?;
+        timed(&clock, Phase::Handshake, write_frame(stream, &reply)).await?;
+        /*-----------HANDSHAKE-----------*/
+
+        loop {
+            let frame = timed(&clock, Phase::Idle, read_frame(stream)).await?;
+            match guarded(|| session.on_frame(&frame)).map_err(rejected)? {
+                Step::Continue(Some(reply)) => timed(&clock, Phase::Idle, write_frame(stream, &reply)).await?,
+                Step::Continue(None) => {}
+                Step::Finish(reply) => return timed(&clock, Phase::Idle, write_frame(stream, &reply)).await,
+                Step::Next(reply) => {
+                    timed(&clock, Phase::Idle, write_frame(stream, &reply)).await?;
+                    break;
+                }
+            }
+        }
+        waiting = Phase::Idle; // the next HELLO may take as long as any frame between messages
+    }
+}
+
+// Runs one frame's I/O, failing with a `timeout` error if it misses the phase's deadline.
+async fn timed<T>(clock: &SessionClock, phase: Phase, io: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+    match clock.deadline(phase) {
+        Some(deadline) => timeout_at(deadline.into(), io).await.unwrap_or_else(|_| Err(clock.expired(phase))),
+        None => io.await,
+    }
+}
+
+// Turns a panic in session code into an internal error for the client.
+fn guarded<T>(step: impl FnOnce() -> Result<T, ServerError>) -> Result<T, ServerError> {
+    catch_unwind(AssertUnwindSafe(step))
+        .unwrap_or_else(|_| Err(ServerError::new(ErrorCode::Internal, "Internal server error")))
+}
+
+async fn write_frame(stream: &mut TcpStream, frame: &Frame) -> io::Result<()> {
+    stream.write_all(&encode_frame(frame)?).await
+}
+
+async fn read_frame(stream: &mut TcpStream) -> io::Result<Frame> {
+    let mut header = [0u8; HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+    let (kind, length) = parse_header(&header)?;
+
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload).await?;
+    Ok(Frame { kind, payload })
+}
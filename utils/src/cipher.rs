@@ -0,0 +1,256 @@
+/*!
+ * -----------------------------------------
+ * Streaming one-time pad cipher.
+ * Consumes interleaved data/key bytes in chunks of any size,
+ * carries a dangling data byte across chunk boundaries,
+ * and emits one output symbol per completed data/key pair.
+ * Symbols are combined modulo the size of the active alphabet,
+ * or XORed byte by byte in binary mode.
+ * In modular mode every data and key symbol is checked against
+ * the alphabet, and the first invalid one stops the stream.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt;
+use std::str::FromStr;
+use crate::error::OtpError;
+use crate::mode::Mode;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Data,
+    Key,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InvalidSymbol {
+    pub(crate) operand: Operand,
+    pub(crate) offset: u64, // position of the symbol in the message
+    pub(crate) byte: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct OtpStream {
+    direction: Direction,
+    mode: Mode,
+    dangling: Option<u8>, // data byte still waiting for its key byte
+    position: u64, // pairs processed so far
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl Direction {
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Encrypt => "encrypt",
+            Direction::Decrypt => "decrypt",
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Direction {
+    type Err = OtpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "encrypt" => Ok(Direction::Encrypt),
+            "decrypt" => Ok(Direction::Decrypt),
+            _ => Err(OtpError::Input(format!("Unknown operation '{}'", s))),
+        }
+    }
+}
+
+impl OtpStream {
+    pub fn new(direction: Direction, mode: Mode) -> Self {
+        OtpStream { direction, mode, dangling: None, position: 0 }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    /// Number of data/key pairs processed so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// True when every data byte fed so far has been paired with a key byte.
+    pub fn is_aligned(&self) -> bool {
+        self.dangling.is_none()
+    }
+
+    /// Feeds interleaved data/key bytes, appending output for every completed pair.
+    /// Returns the number of symbols appended to `output`. On an invalid symbol, output
+    /// for the pairs before it has been appended and the stream should be abandoned.
+    pub fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<usize, OtpError> {
+        let start = output.len();
+        let mut rest = input;
+
+        /*-----------HANDLE DANGLING CHARACTER-----------*/
+        if let Some(data_byte) = self.dangling {
+            match rest.split_first() {
+                Some((&key_byte, tail)) => {
+                    output.push(self.combine(data_byte, key_byte)?);
+                    self.dangling = None;
+                    rest = tail;
+                }
+                None => return Ok(0),
+            }
+        }
+        /*-----------HANDLE DANGLING CHARACTER-----------*/
+
+        /*-----------PROCESS PAIRS-----------*/
+        let mut pairs = rest.chunks_exact(2);
+        for pair in &mut pairs {
+            output.push(self.combine(pair[0], pair[1])?);
+        }
+        if let [data_byte] = pairs.remainder() {
+            self.dangling = Some(*data_byte);
+        }
+        /*-----------PROCESS PAIRS-----------*/
+
+        Ok(output.len() - start)
+    }
+
+    /// Applies the cipher to separate data and key buffers.
+    /// Only the first `min(data.len(), key.len())` symbols are processed.
+    pub fn apply(&mut self, data: &[u8], key: &[u8], output: &mut Vec<u8>) -> Result<usize, OtpError> {
+        let start = output.len();
+        for (&data_byte, &key_byte) in data.iter().zip(key) {
+            output.push(self.combine(data_byte, key_byte)?);
+        }
+        Ok(output.len() - start)
+    }
+
+    fn combine(&mut self, data_byte: u8, key_byte: u8) -> Result<u8, InvalidSymbol> {
+        let result = match &self.mode {
+            Mode::Modular(alphabet) => {
+                let size = alphabet.len();
+                let invalid = |operand, byte| InvalidSymbol { operand, offset: self.position, byte };
+                let data_val = alphabet.index_of(data_byte).ok_or_else(|| invalid(Operand::Data, data_byte))?;
+                let key_val = alphabet.index_of(key_byte).ok_or_else(|| invalid(Operand::Key, key_byte))?;
+                let index = match self.direction {
+                    Direction::Encrypt => (data_val + key_val) % size,
+                    Direction::Decrypt => (data_val + size - key_val) % size,
+                };
+                alphabet.symbols()[index]
+            }
+            Mode::Xor => data_byte ^ key_byte, // XOR is its own inverse
+        };
+        self.position += 1;
+        Ok(result)
+    }
+}
+
+impl Operand {
+    pub fn name(self) -> &'static str {
+        match self {
+            Operand::Data => "data",
+            Operand::Key => "key",
+        }
+    }
+}
+
+impl fmt::Display for InvalidSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {} symbol '{}' (byte: {})", self.operand.name(), self.byte.escape_ascii(), self.byte)
+    }
+}
+
+impl std::error::Error for InvalidSymbol {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabet::Alphabet;
+    use crate::interleave_buffers;
+
+    const DATA: &[u8] = b"HELLO WORLD ATTACK AT DAWN";
+    const KEY: &[u8] = b"XMCKLQWERTYUIOP ZXCVBNMASDFGH";
+
+    fn modes() -> [Mode; 2] {
+        [Mode::Modular(Alphabet::classic()), Mode::Xor]
+    }
+
+    // Feeds `input` to a new stream in pieces, splitting it at each of `splits`.
+    fn feed(direction: Direction, mode: &Mode, input: &[u8], splits: &[usize]) -> Result<Vec<u8>, OtpError> {
+        let mut otp = OtpStream::new(direction, mode.clone());
+        let mut output = Vec::new();
+        let mut start = 0;
+        for &end in splits.iter().chain([input.len()].iter()) {
+            otp.update(&input[start..end], &mut output)?;
+            start = end;
+        }
+        assert!(otp.is_aligned());
+        Ok(output)
+    }
+
+    #[test]
+    fn every_split_matches_one_shot_apply() {
+        for mode in modes() {
+            for direction in [Direction::Encrypt, Direction::Decrypt] {
+                let mut expected = Vec::new();
+                OtpStream::new(direction, mode.clone()).apply(DATA, KEY, &mut expected).unwrap();
+                assert_eq!(expected.len(), DATA.len());
+                let input = interleave_buffers(DATA, KEY);
+                for split in 0..=input.len() {
+                    assert_eq!(feed(direction, &mode, &input, &[split]).unwrap(), expected, "{} {} split {}", mode, direction, split);
+                }
+                let every_byte: Vec<usize> = (1..input.len()).collect();
+                assert_eq!(feed(direction, &mode, &input, &every_byte).unwrap(), expected, "{} {} byte by byte", mode, direction);
+            }
+        }
+    }
+
+    #[test]
+    fn decrypt_inverts_encrypt() {
+        for mode in modes() {
+            let mut ciphertext = Vec::new();
+            OtpStream::new(Direction::Encrypt, mode.clone()).apply(DATA, KEY, &mut ciphertext).unwrap();
+            let mut plaintext = Vec::new();
+            OtpStream::new(Direction::Decrypt, mode.clone()).apply(&ciphertext, KEY, &mut plaintext).unwrap();
+            assert_eq!(plaintext, DATA, "{}", mode);
+        }
+    }
+
+    #[test]
+    fn invalid_symbol_reports_its_offset() {
+        let mode = Mode::Modular(Alphabet::classic());
+        let input = interleave_buffers(b"HELlO", b"XMCKL");
+        for split in 0..=input.len() {
+            match feed(Direction::Encrypt, &mode, &input, &[split]) {
+                Err(OtpError::InvalidSymbol { position, .. }) => assert_eq!(position, 3, "split {}", split),
+                other => panic!("split {}: {:?}", split, other),
+            }
+        }
+
+        let mut output = Vec::new();
+        let mut otp = OtpStream::new(Direction::Decrypt, mode);
+        match otp.apply(b"HELLO", b"XM!KL", &mut output) {
+            Err(OtpError::InvalidSymbol { position, message }) => {
+                assert_eq!(position, 2);
+                assert!(message.contains("key"), "{}", message);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(output.len(), 2); // the pairs before it were still output
+    }
+}
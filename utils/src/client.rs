@@ -0,0 +1,395 @@
+/*!
+ * -----------------------------------------
+ * File-level client for the one-time pad servers.
+ * Reads data and key files (or stdin), verifies key
+ * containers, validates both for the mode, picks the pad
+ * range (reserving it in the key's ledger once the server
+ * accepts the session, or reading it from a ciphertext
+ * envelope), interleaves data and key,
+ * and runs one encrypt or decrypt session against a
+ * server through `OtpClient`, streaming the output to
+ * any writer.
+ * With a server pad, only the data is sent and the
+ * server supplies the key from the pad it holds.
+ * Offline sessions run the same cipher in-process.
+ * Online sessions are bounded by the request's timeouts.
+ * A batch runs one request per file of a directory over
+ * a single connection, each file with its own pad range.
+ * Shared by enc_client, dec_client and otp.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use crate::cipher::{Direction, OtpStream};
+use crate::envelope::Envelope;
+use crate::error::OtpError;
+use crate::handshake::{Hello, PadRef, SERVER_PAD_FEATURE};
+use crate::input::{open_input, scan, trimmed, Input, Spool, STDIN_PATH};
+use crate::key_file::{KeyFile, KeyHeader};
+use crate::ledger::PadLedger;
+use crate::mode::Mode;
+use crate::otp_client::{ClientOptions, OtpClient, Payload};
+use crate::output::open_output;
+use crate::timeout::Timeouts;
+use crate::CHUNK_SIZE;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub const DEFAULT_HOST: &str = "0.0.0.0";
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone)]
+pub struct ClientRequest {
+    pub operation: Direction,
+    pub input: String, // path to the plaintext or ciphertext
+    pub key: String, // path to the key
+    pub host: String, // name, IPv4 or IPv6 literal
+    pub port: String,
+    pub mode: Mode,
+    pub ledger: bool, // reserve pad through the key's ledger and envelope the ciphertext; on by default
+    pub envelope: bool, // envelope the ciphertext even without the ledger
+    pub pad_id: Option<String>, // key ID of a pad held by the server, used instead of `key`
+    pub offset: Option<u64>, // pad offset for server pads; `None` lets the server pick
+    pub offline: bool, // run the cipher in-process instead of on a server
+    pub timeouts: Timeouts, // the handshake timeout also bounds connecting
+    pub trailing_newline: bool, // end text-mode output with a newline; binary output never gets one
+}
+
+/// What a batch did with each file, by file name.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<(String, OtpError)>,
+    pub skipped: Vec<String>, // not attempted once the server could no longer be reached
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl ClientRequest {
+    pub fn new(operation: Direction, input: &str, key: &str, port: &str) -> Self {
+        ClientRequest {
+            operation,
+            input: input.to_string(),
+            key: key.to_string(),
+            host: DEFAULT_HOST.to_string(),
+            port: port.to_string(),
+            mode: Mode::default(),
+            ledger: true,
+            envelope: false,
+            pad_id: None,
+            offset: None,
+            offline: false,
+            timeouts: Timeouts::default(),
+            trailing_newline: true,
+        }
+    }
+}
+
+/// Runs one session for `request`, writing the server's output to `out` as it arrives.
+/// Inputs are streamed in bounded chunks; either one may be `-` to read it from stdin.
+pub fn run_client<W: Write>(request: &ClientRequest, out: &mut W) -> Result<(), OtpError> {
+    run_message(request, &mut None, out)
+}
+
+/// Runs `request` once for every file in `input_dir`, in name order, writing each output
+/// under the same name in `output_dir`. Online, the files share one connection when the
+/// server supports batches. Encrypting takes a new pad range per file, so it needs the
+/// ledger or a server pad. A file that fails doesn't stop the ones after it, unless the
+/// server can't be reached at all.
+pub fn run_batch(request: &ClientRequest, input_dir: &str, output_dir: &str) -> Result<BatchSummary, OtpError> {
+    if request.operation == Direction::Encrypt && !request.ledger && request.pad_id.is_none() {
+        return Err(OtpError::Input("Batch encryption needs the ledger (not --no-ledger) or a server pad, so each file gets its own pad range".to_string()));
+    }
+    if request.offset.is_some() {
+        return Err(OtpError::Input("A batch can't use one pad offset for every file".to_string()));
+    }
+
+    /*-----------LIST INPUTS-----------*/
+    fs::create_dir_all(output_dir).map_err(|e| OtpError::Io("Error creating output directory".to_string(), e))?;
+    let same_dir = fs::canonicalize(input_dir).ok().zip(fs::canonicalize(output_dir).ok()).is_some_and(|(a, b)| a == b);
+    if same_dir {
+        return Err(OtpError::Input("The output directory must not be the input directory".to_string()));
+    }
+    let listing_error = |e| OtpError::Io("Error reading input directory".to_string(), e);
+    let mut names = Vec::new();
+    for entry in fs::read_dir(input_dir).map_err(listing_error)? {
+        let entry = entry.map_err(listing_error)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // hidden files include the temporary files outputs are written under
+        if entry.file_type().map_err(listing_error)?.is_file() && !name.starts_with('.') {
+            names.push(name);
+        }
+    }
+    names.sort();
+    /*-----------LIST INPUTS-----------*/
+
+    /*-----------RUN MESSAGES-----------*/
+    let mut summary = BatchSummary::default();
+    let mut session = None;
+    let mut names = names.into_iter();
+    for name in names.by_ref() {
+        let mut file_request = request.clone();
+        file_request.input = Path::new(input_dir).join(&name).to_string_lossy().into_owned();
+        let output = Path::new(output_dir).join(&name).to_string_lossy().into_owned();
+        // each output is committed on its own, so a failed file leaves nothing behind
+        let result = open_output(Some(&output))
+            .map_err(|e| OtpError::Io("Error opening output".to_string(), e))
+            .and_then(|mut out| {
+                run_message(&file_request, &mut session, &mut out)?;
+                out.commit().map_err(|e| OtpError::Io("Error writing output".to_string(), e))
+            });
+        match result {
+            Ok(()) => summary.succeeded.push(name),
+            Err(e) => {
+                let unreachable = matches!(e, OtpError::Connection(..));
+                summary.failed.push((name, e));
+                if unreachable {
+                    break;
+                }
+            }
+        }
+    }
+    summary.skipped.extend(names);
+    /*-----------RUN MESSAGES-----------*/
+    Ok(summary)
+}
+
+// Runs one session for `request`, reusing the connection in `session` if there is one
+// and leaving the connection there for the next message.
+fn run_message<W: Write>(request: &ClientRequest, session: &mut Option<OtpClient>, out: &mut W) -> Result<(), OtpError> {
+    let (data_name, data_label) = match request.operation {
+        Direction::Encrypt => ("plaintext", "Plaintext"),
+        Direction::Decrypt => ("ciphertext", "Ciphertext"),
+    };
+    let text = !request.mode.is_binary(); // text modes ignore trailing newlines
+    let trailing_newline = text && request.trailing_newline;
+    let data_context = format!("Error reading {} file", data_name);
+
+    /*-----------SPOOL STDIN-----------*/
+    let key_from_stdin = request.pad_id.is_none() && request.key == STDIN_PATH;
+    if key_from_stdin && request.input == STDIN_PATH {
+        return Err(OtpError::Input("Only one of the data and the key can be read from stdin".to_string()));
+    }
+    if key_from_stdin && request.ledger && request.operation == Direction::Encrypt {
+        return Err(OtpError::Input("The pad ledger needs a key file, not stdin (pass --no-ledger to encrypt from offset 0)".to_string()));
+    }
+    // the pad range and envelope take the data's length before anything is sent
+    let needs_length = request.pad_id.is_some() || (request.operation == Direction::Encrypt && (request.ledger || request.envelope));
+    let data_spool = match request.input == STDIN_PATH && needs_length {
+        true => Some(Spool::stdin().map_err(reading(&data_context))?),
+        false => None,
+    };
+    let key_spool = match key_from_stdin {
+        true => Some(Spool::stdin().map_err(reading("Error reading key file"))?), // keys are checked in full before use
+        false => None,
+    };
+    let data_path = data_spool.as_ref().map_or_else(|| request.input.clone(), Spool::path);
+    let key_path = key_spool.as_ref().map_or_else(|| request.key.clone(), Spool::path);
+    /*-----------SPOOL STDIN-----------*/
+
+    /*-----------CHECK KEY-----------*/
+    let key = match request.pad_id {
+        Some(_) => None, // the server holds the key
+        None => Some(check_key(&key_path, &request.mode)?),
+    };
+    let key_id = request.pad_id.clone()
+        .or_else(|| key.as_ref().and_then(|(header, _)| header.as_ref()).map(|header| header.id.clone()));
+    /*-----------CHECK KEY-----------*/
+
+    /*-----------CHECK DATA-----------*/
+    let open_data = || -> Result<(Option<Envelope>, Input), OtpError> {
+        let input = open_input(&data_path).map_err(reading(&data_context))?;
+        if request.operation == Direction::Encrypt {
+            return Ok((None, trimmed(input, text)));
+        }
+        let (envelope, body) = Envelope::read(input).map_err(reading(&data_context))?;
+        Ok((envelope, trimmed(Box::new(body), text)))
+    };
+    let (envelope, mut data) = open_data()?;
+    let mut length = None; // data symbols, when known before sending
+    if data_path != STDIN_PATH {
+        // a file is checked in full first, so bad data fails before anything is written
+        let scanned = scan(&mut data, request.mode.alphabet(), |_| {}).map_err(|e| match e {
+            OtpError::InvalidSymbol { .. } => e.context(&format!("{} contains invalid characters", data_label)),
+            e => e.context(&data_context),
+        })?;
+        length = Some(scanned);
+        data = open_data()?.1;
+    }
+
+    let mut offset = request.offset; // first pad symbol used for this message
+    if let Some(envelope) = &envelope {
+        if let Some(found) = length.filter(|&found| found != envelope.length) {
+            return Err(OtpError::Input(format!(
+                "Error reading ciphertext file: envelope says {} symbols, found {}", envelope.length, found)));
+        }
+        envelope.check(key_id.as_deref(), &request.mode).map_err(reading("Error reading ciphertext file"))?;
+        offset = Some(envelope.offset);
+        length = Some(envelope.length);
+    }
+    /*-----------CHECK DATA-----------*/
+
+    /*-----------CHECK PAD RANGE-----------*/
+    let ledger = match (&key, request.operation, key_from_stdin) {
+        (Some(_), Direction::Encrypt, false) => Some(PadLedger::for_key(&request.key, key_id.as_deref())?),
+        _ => None,
+    };
+    if let Some(ledger) = ledger.as_ref().filter(|_| !request.ledger) {
+        if ledger.consumed(key_id.as_deref())? > 0 {
+            return Err(OtpError::KeyReuse(format!(
+                "Error reserving key material: {} shows this key is partly consumed; encrypting from offset 0 would reuse it (leave out --no-ledger)", ledger.path())));
+        }
+    }
+    if request.pad_id.is_some() && request.offline {
+        return Err(OtpError::Input("A pad held by the server can't be used offline".to_string()));
+    }
+    if request.pad_id.is_some() && request.operation == Direction::Decrypt && offset.is_none() {
+        return Err(OtpError::Input("Decrypting with a server pad needs an enveloped ciphertext or an offset".to_string()));
+    }
+    if let (Some((_, key_length)), Some(length)) = (&key, length) {
+        if offset.unwrap_or(0).saturating_add(length) > *key_length {
+            return Err(OtpError::KeyTooShort("Error: Key is too short".to_string()));
+        }
+    }
+    /*-----------CHECK PAD RANGE-----------*/
+
+    /*-----------BUILD PAYLOAD-----------*/
+    let mut hello = Hello::new(request.operation, request.mode.clone());
+    if let Some(pad_id) = &request.pad_id { // only the data is sent; the server supplies the key
+        let length = length.ok_or_else(unknown_length)?;
+        hello = hello.with_pad(PadRef { key_id: pad_id.clone(), offset, length });
+    }
+    // Reserves the message's range in the ledger, if it takes one, returning the offset to use.
+    // Called once the server has accepted the session, or just before an offline run, so a
+    // refused connection or handshake consumes no pad.
+    let reserve = |offset: Option<u64>| -> Result<Option<u64>, OtpError> {
+        match (&ledger, &key) {
+            (Some(ledger), Some((_, key_length))) if request.ledger => {
+                let length = length.ok_or_else(unknown_length)?;
+                Ok(Some(ledger.reserve(key_id.as_deref(), *key_length, length)?.start))
+            }
+            _ => Ok(offset),
+        }
+    };
+    let open_key = |offset: Option<u64>| -> Result<Option<Input>, OtpError> {
+        if key.is_none() {
+            return Ok(None);
+        }
+        let (_, material) = KeyFile::open(&key_path).map_err(reading("Error reading key file"))?;
+        let mut material = trimmed(material, text);
+        io::copy(&mut material.by_ref().take(offset.unwrap_or(0)), &mut io::sink()).map_err(reading("Error reading key file"))?; // skip to the pad range
+        Ok(Some(material))
+    };
+    let mut payload = Payload {
+        data,
+        key: None, // opened at the pad range once it is reserved
+        alphabet: request.mode.alphabet(),
+        label: data_label,
+        expected: envelope.as_ref().map(|envelope| envelope.length),
+        symbols: 0,
+    };
+    /*-----------BUILD PAYLOAD-----------*/
+
+    let enveloped = request.operation == Direction::Encrypt && (request.ledger || request.envelope || request.pad_id.is_some());
+    let write_envelope = |out: &mut W, offset: Option<u64>| {
+        if !enveloped {
+            return Ok(());
+        }
+        let length = length.ok_or_else(unknown_length)?;
+        let envelope = Envelope::new(offset.unwrap_or(0), length)
+            .with_key_id(key_id.as_deref())
+            .with_mode(&request.mode);
+        out.write_all(&envelope.to_header()).map_err(|e| OtpError::Io("Error writing output".to_string(), e))
+    };
+
+    /*-----------RUN OFFLINE-----------*/
+    if request.offline {
+        let offset = reserve(offset)?;
+        payload.key = open_key(offset)?;
+        write_envelope(out, offset)?;
+        return cipher_locally(request, &mut payload, out, trailing_newline);
+    }
+    /*-----------RUN OFFLINE-----------*/
+
+    /*-----------RUN SESSION-----------*/
+    let client = match session {
+        Some(client) => client,
+        None => {
+            let options = ClientOptions { mode: request.mode.clone(), timeouts: request.timeouts };
+            session.insert(OtpClient::connect_to(&request.host, &request.port, options)?)
+        }
+    };
+    client.run(&hello, &mut payload, out, |accepted, payload, out| {
+        if request.pad_id.is_some() {
+            let pad_offset = accepted.pad.as_ref().and_then(|pad| pad.offset)
+                .filter(|_| accepted.has_feature(SERVER_PAD_FEATURE))
+                .ok_or_else(|| OtpError::HandshakeMismatch("Handshake failed: Server does not hold pads".to_string()))?;
+            offset = Some(pad_offset);
+        } else {
+            offset = reserve(offset)?;
+            payload.key = open_key(offset)?;
+        }
+        write_envelope(out, offset)
+    })?;
+    /*-----------RUN SESSION-----------*/
+
+    let written = if trailing_newline { out.write_all(b"\n") } else { Ok(()) };
+    written.and_then(|_| out.flush()).map_err(|e| OtpError::Io("Error writing output".to_string(), e))
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} files done, {} failed, {} skipped", self.succeeded.len(), self.failed.len(), self.skipped.len())
+    }
+}
+
+// Checks the key at `path` for `mode` without loading it, returning its header
+// (`None` for a legacy key) and its length in symbols.
+fn check_key(path: &str, mode: &Mode) -> Result<(Option<KeyHeader>, u64), OtpError> {
+    if let Some(header) = KeyFile::verify(path).map_err(reading("Error reading key file"))? {
+        header.check_mode(mode).map_err(reading("Error reading key file"))?;
+        let length = header.length;
+        return Ok((Some(header), length));
+    }
+
+    // a legacy key carries no checks of its own, so it is held to the request's mode
+    let (_, material) = KeyFile::open(path).map_err(reading("Error reading key file"))?;
+    let length = scan(&mut trimmed(material, !mode.is_binary()), mode.alphabet(), |_| {}).map_err(|e| match e {
+        OtpError::InvalidSymbol { .. } => e.context("Key contains invalid characters"),
+        e => e.context("Error reading key file"),
+    })?;
+    Ok((None, length))
+}
+
+// Puts `context` in front of an error from reading an input.
+fn reading<E: Into<OtpError>>(context: &str) -> impl Fn(E) -> OtpError + '_ {
+    move |e| e.into().context(context)
+}
+
+// The pad range and envelope need the data's length, which the data is
+// spooled or scanned for whenever they are used.
+fn unknown_length() -> OtpError {
+    OtpError::Input("The data's length is not known before sending it".to_string())
+}
+
+// Runs the cipher in-process on the payload with the same stream the servers
+// use, so the output is identical to a server session's.
+fn cipher_locally<W: Write>(request: &ClientRequest, payload: &mut Payload, out: &mut W, trailing_newline: bool) -> Result<(), OtpError> {
+    let failed = |e| OtpError::Io("Error writing output".to_string(), e);
+    let mut otp = OtpStream::new(request.operation, request.mode.clone());
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    let mut write_buffer = Vec::with_capacity(CHUNK_SIZE);
+    while payload.next_chunk(&mut chunk)? {
+        write_buffer.clear();
+        otp.update(&chunk, &mut write_buffer).map_err(|e| e.context("Offline cipher failed"))?;
+        out.write_all(&write_buffer).map_err(failed)?;
+    }
+    if trailing_newline {
+        out.write_all(b"\n").map_err(failed)?;
+    }
+    out.flush().map_err(failed)
+}
@@ -0,0 +1,140 @@
+/*!
+ * -----------------------------------------
+ * Ciphertext envelope.
+ * A short text header written in front of a ciphertext
+ * that records which key and which range of its pad
+ * encrypted it:
+ *
+ * ```text
+ * OTPMSG 1
+ * key=<key ID, for key containers>
+ * offset=<first pad symbol used>
+ * length=<symbols of ciphertext>
+ * mode=<modular or xor>
+ * alphabet=<alphabet descriptor, in modular mode>
+ * ```
+ *
+ * followed by a blank line and the ciphertext itself.
+ * Only offset and length are required. Ciphertexts
+ * without the magic line are bare legacy ciphertexts
+ * that start at offset 0 of the pad.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt::Write as _;
+use std::io::Read;
+use crate::alphabet::Alphabet;
+use crate::error::OtpError;
+use crate::frame::parse_fields;
+use crate::input::{split_header, Body};
+use crate::mode::Mode;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const MAGIC: &[u8] = b"OTPMSG ";
+const FORMAT_VERSION: u32 = 1;
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub key_id: Option<String>,
+    pub offset: u64,
+    pub length: u64,
+    pub mode: Option<Mode>,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl Envelope {
+    pub fn new(offset: u64, length: u64) -> Self {
+        Envelope { key_id: None, offset, length, mode: None }
+    }
+
+    pub fn with_key_id(mut self, key_id: Option<&str>) -> Self {
+        self.key_id = key_id.map(str::to_string);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: &Mode) -> Self {
+        self.mode = Some(mode.clone());
+        self
+    }
+
+    /// The header to write in front of the ciphertext, blank line included.
+    pub fn to_header(&self) -> Vec<u8> {
+        let mut header = format!("OTPMSG {}\n", FORMAT_VERSION);
+        if let Some(key_id) = &self.key_id {
+            let _ = writeln!(header, "key={}", key_id);
+        }
+        let _ = write!(header, "offset={}\nlength={}\n", self.offset, self.length);
+        match &self.mode {
+            Some(Mode::Modular(alphabet)) => {
+                let _ = write!(header, "mode=modular\nalphabet={}\n", alphabet);
+            }
+            Some(Mode::Xor) => header.push_str("mode=xor\n"),
+            None => {}
+        }
+        header.push('\n');
+        header.into_bytes()
+    }
+
+    /// Checks the envelope against the session's key and mode.
+    /// Fields the envelope doesn't record always pass.
+    pub fn check(&self, key_id: Option<&str>, mode: &Mode) -> Result<(), OtpError> {
+        if let (Some(recorded), Some(key_id)) = (&self.key_id, key_id) {
+            if recorded != key_id {
+                return Err(OtpError::Input(format!("Ciphertext was encrypted with key {}, not {}", recorded, key_id)));
+            }
+        }
+        match &self.mode {
+            Some(recorded) if recorded != mode => Err(OtpError::Input(format!("Ciphertext was encrypted in mode '{}', not '{}'", recorded, mode))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the envelope off the front of a stream. Returns the envelope, or `None`
+    /// for a bare ciphertext, and a reader over the ciphertext body.
+    pub fn read<R: Read>(reader: R) -> Result<(Option<Envelope>, Body<R>), OtpError> {
+        let (header, body) = split_header(reader, MAGIC)?;
+        let envelope = header.map(|header| Envelope::parse(&header)).transpose()?;
+        Ok((envelope, body))
+    }
+
+    // Parses an envelope header, magic line included and blank line excluded.
+    fn parse(header: &[u8]) -> Result<Envelope, OtpError> {
+        let malformed = |message: &str| OtpError::Input(message.to_string());
+
+        /*-----------CHECK VERSION-----------*/
+        let (magic_line, fields) = header.split_at(header.iter().position(|&b| b == b'\n').unwrap_or(header.len()));
+        let version = std::str::from_utf8(&magic_line[MAGIC.len()..]).ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| malformed("Malformed ciphertext envelope version"))?;
+        if version != FORMAT_VERSION {
+            return Err(OtpError::Input(format!("Unsupported ciphertext envelope version {}", version)));
+        }
+        /*-----------CHECK VERSION-----------*/
+
+        /*-----------PARSE FIELDS-----------*/
+        let fields = parse_fields(fields).ok_or_else(|| malformed("Malformed ciphertext envelope"))?;
+        let text = |name: &str| fields.iter().rev().find(|(k, _)| *k == name).map(|(_, v)| *v);
+        let number = |name: &str| text(name).and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| OtpError::Input(format!("Ciphertext envelope is missing or has a malformed '{}'", name)));
+        let mode = match (text("mode"), text("alphabet")) {
+            (None, _) => None,
+            (Some("xor"), _) => Some(Mode::Xor),
+            (Some("modular"), Some(descriptor)) => Some(Mode::Modular(descriptor.parse::<Alphabet>()?)),
+            (Some("modular"), None) => return Err(malformed("Ciphertext envelope has no alphabet for modular mode")),
+            (Some(other), _) => return Err(OtpError::Input(format!("Ciphertext envelope has an unknown mode '{}'", other))),
+        };
+        let envelope = Envelope {
+            key_id: text("key").map(str::to_string),
+            offset: number("offset")?,
+            length: number("length")?,
+            mode,
+        };
+        /*-----------PARSE FIELDS-----------*/
+
+        Ok(envelope)
+    }
+}
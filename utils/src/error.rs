@@ -0,0 +1,163 @@
+/*!
+ * -----------------------------------------
+ * The error type of the utils crate.
+ * Each variant is a class of failure callers can branch on,
+ * whether the client noticed it or the server reported it
+ * in an ERROR frame: a short key is `KeyTooShort` either
+ * way. Every variant carries the full message to print and
+ * maps to the process exit code for its class, which is
+ * how the binaries pick their exit codes.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::error::Error;
+use std::fmt;
+use std::io;
+use crate::cipher::InvalidSymbol;
+use crate::ledger::LedgerError;
+use crate::server_error::{ErrorCode, ServerError};
+use crate::{server_error, EXIT_IO, EXIT_USAGE};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug)]
+pub enum OtpError {
+    /// An input can't be used as given: conflicting options, a malformed
+    /// key container or envelope, a ledger that belongs to another key.
+    Input(String),
+    /// Reading an input or writing the output failed. The context may be empty
+    /// until a caller says what was being read or written.
+    Io(String, io::Error),
+    /// Connecting to the server, or the connection itself, failed.
+    Connection(String, io::Error),
+    /// The server refused the session, or accepted a different one than asked for.
+    HandshakeMismatch(String),
+    /// The peer sent something the wire protocol doesn't allow.
+    Protocol(String),
+    /// A data or key symbol outside the mode's alphabet, at `position` of its input.
+    InvalidSymbol { position: u64, message: String },
+    /// The key ran out before the data did.
+    KeyTooShort(String),
+    /// The session would use key material that was used before.
+    KeyReuse(String),
+    /// A handshake, idle or session timeout ran out.
+    Timeout(String),
+    /// The operating system's entropy source failed while generating a key.
+    Entropy(String),
+    /// Any other error the server reported.
+    Server(String, ServerError),
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl OtpError {
+    /// Sorts a failed network operation into its class. `context` says what was
+    /// being done, such as "Handshake failed", and starts the message.
+    pub(crate) fn session(context: &str, error: io::Error) -> Self {
+        match server_error(&error) {
+            Some(server) => OtpError::server(context, server.clone()),
+            None if error.kind() == io::ErrorKind::InvalidData => OtpError::Protocol(format!("{}: {}", context, error)),
+            None => OtpError::Connection(context.to_string(), error),
+        }
+    }
+
+    /// Sorts an error the server reported into its class.
+    pub(crate) fn server(context: &str, error: ServerError) -> Self {
+        let message = format!("{}: {}", context, error);
+        match error.code {
+            ErrorCode::BadHandshake => OtpError::HandshakeMismatch(message),
+            ErrorCode::Protocol => OtpError::Protocol(message),
+            ErrorCode::InvalidSymbol => OtpError::InvalidSymbol { position: error.offset.unwrap_or(0), message },
+            ErrorCode::KeyTooShort => OtpError::KeyTooShort(message),
+            ErrorCode::KeyReuse => OtpError::KeyReuse(message),
+            ErrorCode::Timeout => OtpError::Timeout(message),
+            _ => OtpError::Server(context.to_string(), error),
+        }
+    }
+
+    /// Puts `context` in front of the message, as in "Error reading key file: ...".
+    pub fn context(self, context: &str) -> Self {
+        let prefixed = |message: String| format!("{}: {}", context, message);
+        match self {
+            OtpError::Io(inner, error) if inner.is_empty() => OtpError::Io(context.to_string(), error),
+            OtpError::Io(inner, error) => OtpError::Io(prefixed(inner), error),
+            OtpError::Connection(inner, error) => OtpError::Connection(prefixed(inner), error),
+            OtpError::Server(inner, error) => OtpError::Server(prefixed(inner), error),
+            OtpError::Input(message) => OtpError::Input(prefixed(message)),
+            OtpError::HandshakeMismatch(message) => OtpError::HandshakeMismatch(prefixed(message)),
+            OtpError::Protocol(message) => OtpError::Protocol(prefixed(message)),
+            OtpError::InvalidSymbol { position, message } => OtpError::InvalidSymbol { position, message: prefixed(message) },
+            OtpError::KeyTooShort(message) => OtpError::KeyTooShort(prefixed(message)),
+            OtpError::KeyReuse(message) => OtpError::KeyReuse(prefixed(message)),
+            OtpError::Timeout(message) => OtpError::Timeout(prefixed(message)),
+            OtpError::Entropy(message) => OtpError::Entropy(prefixed(message)),
+        }
+    }
+
+    /// Process exit code for this class of error; see the README for the table.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            OtpError::Input(_) | OtpError::Io(..) => EXIT_USAGE,
+            OtpError::Connection(..) | OtpError::Entropy(_) => EXIT_IO,
+            OtpError::HandshakeMismatch(_) => ErrorCode::BadHandshake.exit_code(),
+            OtpError::Protocol(_) => ErrorCode::Protocol.exit_code(),
+            OtpError::InvalidSymbol { .. } => ErrorCode::InvalidSymbol.exit_code(),
+            OtpError::KeyTooShort(_) => ErrorCode::KeyTooShort.exit_code(),
+            OtpError::KeyReuse(_) => ErrorCode::KeyReuse.exit_code(),
+            OtpError::Timeout(_) => ErrorCode::Timeout.exit_code(),
+            OtpError::Server(_, error) => error.code.exit_code(),
+        }
+    }
+}
+
+impl From<io::Error> for OtpError {
+    fn from(error: io::Error) -> Self {
+        OtpError::Io(String::new(), error)
+    }
+}
+
+impl From<LedgerError> for OtpError {
+    fn from(error: LedgerError) -> Self {
+        let message = format!("Error reserving key material: {}", error);
+        match error {
+            LedgerError::Io(e) => OtpError::Io("Error reserving key material".to_string(), e),
+            LedgerError::Mismatch(_) => OtpError::Input(message),
+            LedgerError::Reuse(_) => OtpError::KeyReuse(message),
+            LedgerError::Exhausted { .. } => OtpError::KeyTooShort(message),
+        }
+    }
+}
+
+impl From<InvalidSymbol> for OtpError {
+    fn from(invalid: InvalidSymbol) -> Self {
+        OtpError::InvalidSymbol { position: invalid.offset, message: invalid.to_string() }
+    }
+}
+
+impl fmt::Display for OtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpError::Io(context, error) if context.is_empty() => write!(f, "{}", error),
+            OtpError::Io(context, error) | OtpError::Connection(context, error) => write!(f, "{}: {}", context, error),
+            OtpError::Server(context, error) => write!(f, "{}: {}", context, error),
+            OtpError::Input(message)
+            | OtpError::HandshakeMismatch(message)
+            | OtpError::Protocol(message)
+            | OtpError::InvalidSymbol { message, .. }
+            | OtpError::KeyTooShort(message)
+            | OtpError::KeyReuse(message)
+            | OtpError::Timeout(message)
+            | OtpError::Entropy(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for OtpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OtpError::Io(_, error) | OtpError::Connection(_, error) => Some(error),
+            OtpError::Server(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
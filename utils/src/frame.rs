@@ -0,0 +1,125 @@
+/*!
+ * -----------------------------------------
+ * Length-prefixed framing for the client/server protocol.
+ * Every message is a one-byte frame type, a four-byte
+ * big-endian payload length, and the payload itself,
+ * so no byte value is reserved for signalling.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::io::{self, Read, Write};
+use crate::server_error::{ErrorCode, ServerError};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub(crate) const HEADER_LEN: usize = 5;
+pub const MAX_FRAME_PAYLOAD: usize = 64 * 1024;
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Hello,
+    Data,
+    End,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub payload: Vec<u8>,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl FrameKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            FrameKind::Hello => 1,
+            FrameKind::Data => 2,
+            FrameKind::End => 3,
+            FrameKind::Error => 4,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(FrameKind::Hello),
+            2 => Some(FrameKind::Data),
+            3 => Some(FrameKind::End),
+            4 => Some(FrameKind::Error),
+            _ => None,
+        }
+    }
+}
+
+impl Frame {
+    pub fn new(kind: FrameKind, payload: Vec<u8>) -> Self {
+        Frame { kind, payload }
+    }
+
+    pub fn hello(payload: Vec<u8>) -> Self {
+        Self::new(FrameKind::Hello, payload)
+    }
+
+    pub fn data(payload: &[u8]) -> Self {
+        Self::new(FrameKind::Data, payload.to_vec())
+    }
+
+    pub fn end() -> Self {
+        Self::new(FrameKind::End, Vec::new())
+    }
+
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    writer.write_all(&encode_frame(frame)?)
+}
+
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Frame> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let (kind, length) = parse_header(&header)?;
+
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)?;
+    Ok(Frame { kind, payload })
+}
+
+/// Header and payload in one buffer, so they go out in a single write
+/// and small frames aren't split into two segments.
+pub(crate) fn encode_frame(frame: &Frame) -> io::Result<Vec<u8>> {
+    if frame.payload.len() > MAX_FRAME_PAYLOAD {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Frame payload too large"));
+    }
+    let mut buffer = Vec::with_capacity(HEADER_LEN + frame.payload.len());
+    buffer.push(frame.kind.to_byte());
+    buffer.extend_from_slice(&(frame.payload.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(&frame.payload);
+    Ok(buffer)
+}
+
+/// Decodes a frame header into the frame type and payload length.
+pub(crate) fn parse_header(header: &[u8; HEADER_LEN]) -> io::Result<(FrameKind, usize)> {
+    let kind = FrameKind::from_byte(header[0]).ok_or_else(|| {
+        let error = ServerError::new(ErrorCode::Protocol, &format!("Unknown frame type {}", header[0]));
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    })?;
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if length > MAX_FRAME_PAYLOAD {
+        let error = ServerError::new(ErrorCode::Oversized, &format!("Frame of {} bytes exceeds the {} byte limit", length, MAX_FRAME_PAYLOAD));
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+    Ok((kind, length))
+}
+
+/// Splits a payload of newline-separated `key=value` fields.
+/// Returns `None` if the payload isn't UTF-8 or a line has no `=`.
+pub(crate) fn parse_fields(payload: &[u8]) -> Option<Vec<(&str, &str)>> {
+    std::str::from_utf8(payload).ok()?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split_once('='))
+        .collect()
+}
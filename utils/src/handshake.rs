@@ -0,0 +1,190 @@
+/*!
+ * -----------------------------------------
+ * Versioned handshake messages.
+ * The client's HELLO names the protocol version, the operation
+ * it wants, the cipher mode and any optional features.
+ * With the server-pad feature it also names a pad held by
+ * the server, and the server's answer fixes the offset used.
+ * With the batch feature, another HELLO may follow each
+ * END on the same connection to start the next message.
+ * The server answers with a HELLO describing what it accepted,
+ * or an ERROR frame carrying the rejection reason.
+ * Payloads are newline-separated `key=value` fields.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt;
+use crate::cipher::Direction;
+use crate::frame::{parse_fields, Frame, FrameKind};
+use crate::mode::Mode;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub const PROTOCOL_VERSION: u32 = 1;
+const MIN_PROTOCOL_VERSION: u32 = 1;
+pub const SERVER_PAD_FEATURE: &str = "server-pad";
+pub const BATCH_FEATURE: &str = "batch";
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub version: u32,
+    pub operation: Direction,
+    pub mode: Mode,
+    pub features: Vec<String>,
+    pub pad: Option<PadRef>, // set with the server-pad feature
+}
+
+/// A range of a pad held by the server, named by key ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PadRef {
+    pub key_id: String,
+    pub offset: Option<u64>, // `None` lets the server pick the next unused range
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    Malformed,
+    UnsupportedVersion,
+    UnsupportedOperation,
+    UnsupportedMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub reason: RejectReason,
+    pub message: String,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl Hello {
+    pub fn new(operation: Direction, mode: Mode) -> Self {
+        Hello { version: PROTOCOL_VERSION, operation, mode, features: Vec::new(), pad: None }
+    }
+
+    /// Asks the server to supply the key from one of its pads.
+    pub fn with_pad(self, pad: PadRef) -> Self {
+        Hello { pad: Some(pad), ..self.with_feature(SERVER_PAD_FEATURE) }
+    }
+
+    pub fn with_feature(mut self, feature: &str) -> Self {
+        self.features.push(feature.to_string());
+        self
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    pub fn to_frame(&self) -> Frame {
+        let mut payload = format!(
+            "version={}\noperation={}\nmode={}\nfeatures={}\n",
+            self.version, self.operation, self.mode, self.features.join(",")
+        );
+        if let Some(pad) = &self.pad {
+            payload.push_str(&format!("pad-key={}\npad-length={}\n", pad.key_id, pad.length));
+            if let Some(offset) = pad.offset {
+                payload.push_str(&format!("pad-offset={}\n", offset));
+            }
+        }
+        Frame::hello(payload.into_bytes())
+    }
+
+    pub fn from_frame(frame: &Frame) -> Result<Self, Rejection> {
+        if frame.kind != FrameKind::Hello {
+            return Err(Rejection::new(RejectReason::Malformed, "Expected a HELLO frame"));
+        }
+        let (mut version, mut operation, mut mode, mut features) = (None, None, None, Vec::new());
+        let (mut pad_key, mut pad_offset, mut pad_length) = (None, None, None);
+        let fields = parse_fields(&frame.payload).ok_or_else(|| Rejection::new(RejectReason::Malformed, "HELLO is not a list of key=value fields"))?;
+        for (key, value) in fields {
+            match key {
+                "version" => version = value.parse::<u32>().ok(),
+                "operation" => operation = Some(value.parse::<Direction>().map_err(|e| Rejection::new(RejectReason::UnsupportedOperation, &e.to_string()))?),
+                "mode" => mode = Some(value.parse::<Mode>().map_err(|e| Rejection::new(RejectReason::UnsupportedMode, &e.to_string()))?),
+                "features" => features = value.split(',').filter(|f| !f.is_empty()).map(str::to_string).collect(),
+                "pad-key" => pad_key = Some(value.to_string()),
+                "pad-offset" => pad_offset = Some(value.parse::<u64>().map_err(|_| Rejection::new(RejectReason::Malformed, "Malformed pad-offset"))?),
+                "pad-length" => pad_length = Some(value.parse::<u64>().map_err(|_| Rejection::new(RejectReason::Malformed, "Malformed pad-length"))?),
+                _ => {} // unknown fields are ignored so newer peers can add them
+            }
+        }
+        match (version, operation, mode) {
+            (Some(version), Some(operation), Some(mode)) => {
+                let pad = match (pad_key, pad_length) {
+                    (Some(key_id), Some(length)) => Some(PadRef { key_id, offset: pad_offset, length }),
+                    (None, None) => None,
+                    _ => return Err(Rejection::new(RejectReason::Malformed, "HELLO names a pad without both pad-key and pad-length")),
+                };
+                Ok(Hello { version, operation, mode, features, pad })
+            }
+            _ => Err(Rejection::new(RejectReason::Malformed, "HELLO is missing version, operation or mode")),
+        }
+    }
+}
+
+/// Decides how the server answers a client's HELLO.
+/// The agreed version is the lower of the two sides' versions; unknown features are dropped.
+pub fn negotiate(request: &Hello, operations: &[Direction], mode: &Mode, features: &[&str]) -> Result<Hello, Rejection> {
+    if request.version < MIN_PROTOCOL_VERSION {
+        return Err(Rejection::new(
+            RejectReason::UnsupportedVersion,
+            &format!("Protocol version {} is older than the oldest supported version {}", request.version, MIN_PROTOCOL_VERSION),
+        ));
+    }
+    if !operations.contains(&request.operation) {
+        return Err(Rejection::new(
+            RejectReason::UnsupportedOperation,
+            &format!("Server does not {}", request.operation),
+        ));
+    }
+    if request.mode != *mode {
+        return Err(Rejection::new(
+            RejectReason::UnsupportedMode,
+            &format!("Server does not use mode '{}'", request.mode),
+        ));
+    }
+    Ok(Hello {
+        version: request.version.min(PROTOCOL_VERSION),
+        operation: request.operation,
+        mode: mode.clone(),
+        features: request.features.iter().filter(|f| features.contains(&f.as_str())).cloned().collect(),
+        pad: None, // the server fills in the pad once it has opened it
+    })
+}
+
+impl RejectReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            RejectReason::Malformed => "malformed",
+            RejectReason::UnsupportedVersion => "unsupported-version",
+            RejectReason::UnsupportedOperation => "unsupported-operation",
+            RejectReason::UnsupportedMode => "unsupported-mode",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "malformed" => Some(RejectReason::Malformed),
+            "unsupported-version" => Some(RejectReason::UnsupportedVersion),
+            "unsupported-operation" => Some(RejectReason::UnsupportedOperation),
+            "unsupported-mode" => Some(RejectReason::UnsupportedMode),
+            _ => None,
+        }
+    }
+}
+
+impl Rejection {
+    pub fn new(reason: RejectReason, message: &str) -> Self {
+        Rejection { reason, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.reason.name())
+    }
+}
@@ -0,0 +1,329 @@
+/*!
+ * -----------------------------------------
+ * Streaming input for the clients.
+ * Inputs are files, or stdin when named `-`, and are read
+ * in bounded chunks so memory stays flat whatever their
+ * size. Text modes ignore trailing newlines, which a
+ * stream can't see until it ends, so they are held back
+ * until more data shows they weren't trailing after all.
+ * When a session needs an input's length before sending
+ * it, stdin is spooled to a temporary file first. Spools
+ * can hold key material, so they are private to the user,
+ * have unguessable names, and are removed on SIGINT, SIGTERM
+ * and SIGHUP as well as on a normal exit.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Chain, Cursor, Read};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use crate::alphabet::Alphabet;
+use crate::error::OtpError;
+use crate::keygen::fill_random;
+use crate::CHUNK_SIZE;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub const STDIN_PATH: &str = "-";
+const MAX_HEADER: usize = 4096; // longest text header accepted in front of a key or ciphertext
+const SPOOL_NAME_BYTES: usize = 16; // random bytes in a spool's file name
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+// Spools not yet removed, for the signal handler to clean up.
+static SPOOLS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/*-----------TYPE DEFINITIONS-----------*/
+pub(crate) type Input = Box<dyn Read + Send>;
+
+/// What follows a text header: any bytes read ahead while looking for it, then the rest of `R`.
+pub(crate) type Body<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// A copy of stdin in a temporary file, removed when dropped.
+pub(crate) struct Spool {
+    path: PathBuf,
+}
+
+/// Passes bytes through, except that newlines at the very end are dropped.
+pub(crate) struct TrimNewlines<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    position: usize, // next unread byte of `buffer`
+    held: usize, // newlines read but not yet passed on
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+/// Opens `path` for reading, or stdin for `-`.
+pub(crate) fn open_input(path: &str) -> io::Result<Input> {
+    if path == STDIN_PATH {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+/// Wraps `input` to drop trailing newlines when `trim` is set, as text modes do.
+pub(crate) fn trimmed(input: Input, trim: bool) -> Input {
+    if trim {
+        Box::new(TrimNewlines::new(input))
+    } else {
+        input
+    }
+}
+
+/// Reads until `buffer` is full or the input ends, returning the bytes read.
+pub(crate) fn read_chunk<R: Read + ?Sized>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Reads `reader` to the end, checking every symbol against `alphabet` if there is one
+/// and passing each chunk to `inspect`. Returns the number of bytes read.
+pub(crate) fn scan<R: Read + ?Sized>(reader: &mut R, alphabet: Option<&Alphabet>, mut inspect: impl FnMut(&[u8])) -> Result<u64, OtpError> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut length = 0;
+    loop {
+        let n = read_chunk(reader, &mut buffer)?;
+        if n == 0 {
+            return Ok(length);
+        }
+        if let Some(alphabet) = alphabet {
+            alphabet.validate_at(&buffer[..n], length)?;
+        }
+        inspect(&buffer[..n]);
+        length += n as u64;
+    }
+}
+
+/// Splits a text header that starts with `magic` and ends with a blank line off the front
+/// of `reader`. Returns the header without the blank line, if there is one, and a reader
+/// over everything after it; an input without the magic comes back whole.
+pub(crate) fn split_header<R: Read>(mut reader: R, magic: &[u8]) -> io::Result<(Option<Vec<u8>>, Body<R>)> {
+    let mut prefix = vec![0u8; magic.len()];
+    let n = read_chunk(&mut reader, &mut prefix)?;
+    prefix.truncate(n);
+    if prefix != magic {
+        return Ok((None, Cursor::new(prefix).chain(reader)));
+    }
+
+    /*-----------READ UNTIL BLANK LINE-----------*/
+    let mut header = prefix;
+    let mut byte = [0u8; 1];
+    while !header.ends_with(b"\n\n") {
+        if header.len() >= MAX_HEADER || read_chunk(&mut reader, &mut byte)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Header is not terminated by a blank line"));
+        }
+        header.push(byte[0]);
+    }
+    header.truncate(header.len() - 2);
+    /*-----------READ UNTIL BLANK LINE-----------*/
+
+    Ok((Some(header), Cursor::new(Vec::new()).chain(reader)))
+}
+
+impl Spool {
+    /// Copies stdin to a new temporary file.
+    pub(crate) fn stdin() -> io::Result<Self> {
+        let mut random = [0u8; SPOOL_NAME_BYTES];
+        fill_random(&mut random).map_err(io::Error::other)?;
+        let name = random.iter().fold(String::from("otp-"), |mut name, b| {
+            let _ = write!(name, "{:02x}", b);
+            name
+        });
+        let path = env::temp_dir().join(name + ".spool");
+
+        /*-----------CREATE PRIVATE FILE-----------*/
+        let mut options = File::options();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600); // readable by this user only, from the moment it exists
+        let mut file = options.open(&path)?;
+        remove_on_signal();
+        spools().push(path.clone());
+        /*-----------CREATE PRIVATE FILE-----------*/
+
+        let spool = Spool { path }; // removed on drop from here on
+        io::copy(&mut io::stdin().lock(), &mut file)?;
+        Ok(spool)
+    }
+
+    pub(crate) fn path(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        spools().retain(|path| *path != self.path);
+    }
+}
+
+// A panic elsewhere can't leave the list half-updated, so a poisoned lock is still usable.
+fn spools() -> MutexGuard<'static, Vec<PathBuf>> {
+    SPOOLS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Starts, once, a thread that removes the spools and exits when the process is
+// told to stop, since `Drop` doesn't run then. SIGKILL can't be caught; a spool
+// it leaves behind is still readable only by this user.
+fn remove_on_signal() {
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+        use signal_hook::iterator::Signals;
+        static STARTED: std::sync::Once = std::sync::Once::new();
+        STARTED.call_once(|| {
+            let Ok(mut signals) = Signals::new([SIGINT, SIGTERM, SIGHUP]) else { return };
+            std::thread::spawn(move || {
+                if let Some(signal) = signals.forever().next() {
+                    for path in spools().drain(..) {
+                        let _ = fs::remove_file(path);
+                    }
+                    std::process::exit(128 + signal); // as a shell reports a process killed by `signal`
+                }
+            });
+        });
+    }
+}
+
+impl<R: Read> TrimNewlines<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        TrimNewlines { inner, buffer: Vec::with_capacity(CHUNK_SIZE), position: 0, held: 0 }
+    }
+}
+
+impl<R: Read> Read for TrimNewlines<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            /*-----------REFILL-----------*/
+            if self.position == self.buffer.len() {
+                self.buffer.resize(CHUNK_SIZE, 0);
+                let read = self.inner.read(&mut self.buffer);
+                self.buffer.truncate(*read.as_ref().unwrap_or(&0)); // an error leaves nothing to pass on
+                self.position = 0;
+                let n = read?;
+                if n == 0 {
+                    return Ok(0); // any held newlines were trailing
+                }
+                // newlines at the front continue the run being held
+                self.position = self.buffer.iter().position(|&b| b != b'\n').unwrap_or(n);
+                self.held += self.position;
+                continue;
+            }
+            /*-----------REFILL-----------*/
+
+            // more data follows, so the held newlines weren't trailing
+            if self.held > 0 {
+                let n = self.held.min(out.len());
+                out[..n].fill(b'\n');
+                self.held -= n;
+                return Ok(n);
+            }
+
+            // pass on everything up to the last non-newline; hold the rest
+            let unread = &self.buffer[self.position..];
+            let end = unread.iter().rposition(|&b| b != b'\n').map_or(0, |i| i + 1);
+            let n = end.min(out.len());
+            out[..n].copy_from_slice(&unread[..n]);
+            self.position += n;
+            if n == end {
+                self.held += self.buffer.len() - self.position;
+                self.position = self.buffer.len();
+            }
+            return Ok(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trim_newlines;
+
+    // Yields `data` at most `piece` bytes per read, to split it at awkward places.
+    struct Pieces<'a> {
+        data: &'a [u8],
+        piece: usize,
+    }
+
+    impl Read for Pieces<'_> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let n = self.piece.min(out.len()).min(self.data.len());
+            out[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    // Reads `data` through TrimNewlines, `piece` bytes per inner read and `out_len` per outer read.
+    fn trim(data: &[u8], piece: usize, out_len: usize) -> Vec<u8> {
+        let mut reader = TrimNewlines::new(Pieces { data, piece });
+        let mut out = vec![0u8; out_len];
+        let mut trimmed = Vec::new();
+        loop {
+            match reader.read(&mut out).unwrap() {
+                0 => return trimmed,
+                n => trimmed.extend_from_slice(&out[..n]),
+            }
+        }
+    }
+
+    // Checks every split against trimming the whole input at once.
+    fn check(data: &[u8]) {
+        for piece in [1, 2, 3, CHUNK_SIZE - 1, CHUNK_SIZE] {
+            for out_len in [1, 2, 5, CHUNK_SIZE] {
+                assert_eq!(trim(data, piece, out_len), trim_newlines(data), "piece {} out {}", piece, out_len);
+            }
+        }
+    }
+
+    #[test]
+    fn drops_input_of_only_newlines() {
+        check(b"");
+        check(b"\n");
+        check(b"\n\n\n\n");
+    }
+
+    #[test]
+    fn drops_trailing_newlines_only() {
+        check(b"HELLO\n");
+        check(b"HELLO\n\n\n");
+        check(b"\n\nHELLO WORLD\n\nAGAIN\n");
+    }
+
+    #[test]
+    fn keeps_newlines_straddling_a_refill() {
+        let mut data = vec![b'A'; CHUNK_SIZE - 2];
+        data.extend_from_slice(b"\n\n\n\nB\n\n");
+        check(&data);
+        let mut data = vec![b'A'; CHUNK_SIZE];
+        data.extend_from_slice(&[b'\n'; CHUNK_SIZE + 3]);
+        data.push(b'B');
+        check(&data);
+        data.extend_from_slice(&[b'\n'; CHUNK_SIZE]);
+        check(&data);
+    }
+
+    #[test]
+    fn passes_held_newlines_through_a_small_buffer() {
+        assert_eq!(trim(b"AB\n\n\nCD\n\n", 1, 1), b"AB\n\n\nCD");
+        assert_eq!(trim(b"AB\n\n\nCD\n\n", CHUNK_SIZE, 2), b"AB\n\n\nCD");
+    }
+}
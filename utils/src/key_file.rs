@@ -0,0 +1,237 @@
+/*!
+ * -----------------------------------------
+ * Key container format.
+ * A container is a short text header followed by a blank
+ * line and the raw key material:
+ *
+ * ```text
+ * OTPKEY 1
+ * id=<32 hex digits>
+ * mode=<mode descriptor>
+ * length=<symbols of key material>
+ * created=<unix seconds>
+ * checksum=crc32:<8 hex digits>
+ * ```
+ *
+ * The checksum covers the key material only. Text-mode
+ * containers may end with newlines after the material.
+ * Files without the magic line are legacy plain keys and
+ * are returned as-is, without any checks.
+ * Keys can also be opened as a stream, for keys too large
+ * to hold in memory, or read one range at a time.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Take};
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::OtpError;
+use crate::frame::parse_fields;
+use crate::input::{open_input, scan, split_header, trimmed};
+use crate::keygen::{fill_random, generate_key};
+use crate::mode::Mode;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const MAGIC: &[u8] = b"OTPKEY ";
+const FORMAT_VERSION: u32 = 1;
+const KEY_ID_BYTES: usize = 16;
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyHeader {
+    pub id: String, // lowercase hex
+    pub mode: Mode,
+    pub length: u64,
+    pub created: u64, // seconds since the Unix epoch
+    pub checksum: u32, // CRC-32 of the key material
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyFile {
+    pub header: Option<KeyHeader>, // `None` for legacy plain keys
+    pub material: Vec<u8>,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl KeyFile {
+    /// Generates a new key of `length` symbols wrapped in a container.
+    pub fn generate(length: usize, mode: &Mode) -> Result<Self, OtpError> {
+        let material = generate_key(length, mode)?;
+        let mut id = [0u8; KEY_ID_BYTES];
+        fill_random(&mut id)?;
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let header = KeyHeader {
+            id: to_hex(&id),
+            mode: mode.clone(),
+            length: material.len() as u64,
+            created,
+            checksum: crc32(&material),
+        };
+        Ok(KeyFile { header: Some(header), material })
+    }
+
+    /// Opens the key at `path`, or stdin for `-`, as a stream: the container header, if any,
+    /// and a reader over the key material. Nothing is verified; see `verify`.
+    pub fn open(path: &str) -> Result<(Option<KeyHeader>, Box<dyn Read + Send>), OtpError> {
+        let (header, material) = split_header(open_input(path)?, MAGIC)?;
+        let header = header.map(|header| KeyHeader::parse(&header)).transpose()?;
+        Ok((header, Box::new(material)))
+    }
+
+    /// Opens the symbols in `range` of the key material at `path` for reading, seeking
+    /// past the rest. Nothing is verified beyond the range being inside the file.
+    pub fn open_range(path: &str, range: Range<u64>) -> Result<Take<File>, OtpError> {
+        let mut file = File::open(path)?;
+        // the header is read up to its blank line and no further, leaving `file` at the material
+        let (header, _) = split_header(&file, MAGIC)?;
+        let start = match header {
+            Some(_) => SeekFrom::Current(i64::try_from(range.start).map_err(|_| truncated(range.end, 0))?),
+            None => SeekFrom::Start(range.start),
+        };
+        let position = file.seek(start)?;
+        let length = range.end - range.start;
+        if file.metadata()?.len().saturating_sub(position) < length {
+            return Err(OtpError::Input(format!("Key ends before symbol {}", range.end)));
+        }
+        Ok(file.take(length))
+    }
+
+    /// Checks the container at `path`: its length, its checksum and that every symbol
+    /// belongs to its mode, streaming the material rather than loading it. Returns its
+    /// header, or `None` for a legacy key, which has nothing to check.
+    pub fn verify(path: &str) -> Result<Option<KeyHeader>, OtpError> {
+        let (Some(header), material) = KeyFile::open(path)? else {
+            return Ok(None);
+        };
+
+        /*-----------VERIFY MATERIAL-----------*/
+        let mut crc = !0;
+        let mut invalid = None; // reported after the length and checksum
+        let mut position = 0;
+        let alphabet = header.mode.alphabet();
+        let length = scan(&mut trimmed(material, !header.mode.is_binary()), None, |chunk| {
+            crc = crc32_update(crc, chunk);
+            if let (Some(alphabet), None) = (alphabet, &invalid) {
+                invalid = alphabet.validate_at(chunk, position).err();
+            }
+            position += chunk.len() as u64;
+        })?;
+        if length != header.length {
+            return Err(truncated(header.length, length));
+        }
+        if !crc != header.checksum {
+            return Err(corrupt());
+        }
+        /*-----------VERIFY MATERIAL-----------*/
+
+        match invalid {
+            Some(invalid) => Err(invalid),
+            None => Ok(Some(header)),
+        }
+    }
+
+    /// Serializes the key; containers in text modes end with a newline.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let Some(header) = &self.header else {
+            return self.material.clone();
+        };
+        let mut text = format!("OTPKEY {}\n", FORMAT_VERSION);
+        let _ = write!(text, "id={}\nmode={}\nlength={}\ncreated={}\nchecksum=crc32:{:08x}\n\n",
+            header.id, header.mode, header.length, header.created, header.checksum);
+        let mut bytes = text.into_bytes();
+        bytes.extend_from_slice(&self.material);
+        if !header.mode.is_binary() {
+            bytes.push(b'\n');
+        }
+        bytes
+    }
+}
+
+impl KeyHeader {
+    /// Parses a container header, magic line included and blank line excluded.
+    fn parse(header: &[u8]) -> Result<Self, OtpError> {
+        let malformed = |message: &str| OtpError::Input(message.to_string());
+
+        /*-----------CHECK VERSION-----------*/
+        let (magic_line, fields) = header.split_at(header.iter().position(|&b| b == b'\n').unwrap_or(header.len()));
+        let version = std::str::from_utf8(&magic_line[MAGIC.len()..]).ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| malformed("Malformed key header version"))?;
+        if version != FORMAT_VERSION {
+            return Err(OtpError::Input(format!("Unsupported key format version {}", version)));
+        }
+        /*-----------CHECK VERSION-----------*/
+
+        /*-----------PARSE FIELDS-----------*/
+        let fields = parse_fields(fields).ok_or_else(|| malformed("Malformed key header"))?;
+        let field = |name: &str| fields.iter().rev().find(|(k, _)| *k == name).map(|(_, v)| *v)
+            .ok_or_else(|| OtpError::Input(format!("Key header is missing '{}'", name)));
+        let id = field("id")?.to_string();
+        if id.len() != KEY_ID_BYTES * 2 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(OtpError::Input(format!("Malformed key ID '{}'", id)));
+        }
+        let mode: Mode = field("mode")?.parse()?;
+        let length: u64 = field("length")?.parse().map_err(|_| malformed("Malformed key length"))?;
+        let created: u64 = field("created")?.parse().map_err(|_| malformed("Malformed key creation time"))?;
+        let checksum = field("checksum")?.strip_prefix("crc32:")
+            .and_then(|c| u32::from_str_radix(c, 16).ok())
+            .ok_or_else(|| malformed("Malformed key checksum"))?;
+        /*-----------PARSE FIELDS-----------*/
+
+        Ok(KeyHeader { id, mode, length, created, checksum })
+    }
+
+    /// Checks that the key was generated for `mode`.
+    pub fn check_mode(&self, mode: &Mode) -> Result<(), OtpError> {
+        if self.mode != *mode {
+            return Err(OtpError::Input(format!("Key was generated for mode '{}', not '{}'", self.mode, mode)));
+        }
+        Ok(())
+    }
+}
+
+// The errors for container material that doesn't match its header.
+fn truncated(expected: u64, found: u64) -> OtpError {
+    OtpError::Input(format!("Key is truncated or padded: header says {} symbols, found {}", expected, found))
+}
+
+fn corrupt() -> OtpError {
+    OtpError::Input("Key checksum mismatch: the key file is corrupt".to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{:02x}", b);
+        hex
+    })
+}
+
+// CRC-32 (IEEE 802.3, reflected polynomial 0xEDB88320).
+fn crc32(bytes: &[u8]) -> u32 {
+    !crc32_update(!0, bytes)
+}
+
+// Continues a CRC-32 from the running `crc`; start from `!0` and invert the result.
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    bytes.iter().fold(crc, |crc, &b| (crc >> 8) ^ TABLE[((crc ^ b as u32) & 0xFF) as usize])
+}
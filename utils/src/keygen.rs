@@ -0,0 +1,66 @@
+/*!
+ * -----------------------------------------
+ * Key generation.
+ * Every byte comes straight from the operating system's
+ * entropy source. Text modes map bytes onto the alphabet
+ * by rejection sampling: a byte is used only if it falls
+ * below the largest multiple of the alphabet size, so each
+ * symbol is exactly equally likely. Xor mode uses the raw bytes.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use crate::error::OtpError;
+use crate::key_file::KeyFile;
+use crate::mode::Mode;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const ENTROPY_BATCH: usize = 4096; // bytes requested from the OS per call
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/// Fills `buffer` with bytes from the operating system's entropy source.
+pub fn fill_random(buffer: &mut [u8]) -> Result<(), OtpError> {
+    getrandom::getrandom(buffer).map_err(|e| OtpError::Entropy(format!("Entropy source failed: {}", e)))
+}
+
+/// Generates `length` key symbols for `mode`, without a trailing newline.
+pub fn generate_key(length: usize, mode: &Mode) -> Result<Vec<u8>, OtpError> {
+    let alphabet = match mode.alphabet() {
+        Some(alphabet) => alphabet,
+        None => {
+            let mut key = vec![0u8; length];
+            fill_random(&mut key)?;
+            return Ok(key);
+        }
+    };
+
+    /*-----------REJECTION SAMPLING-----------*/
+    let size = alphabet.len();
+    let limit = 256 - 256 % size; // bytes at or above this would favour the first symbols
+    let mut key = Vec::with_capacity(length);
+    let mut batch = [0u8; ENTROPY_BATCH];
+    while key.len() < length {
+        fill_random(&mut batch)?;
+        let accepted = batch.iter()
+            .map(|&b| b as usize)
+            .filter(|&b| b < limit)
+            .map(|b| alphabet.symbols()[b % size]);
+        key.extend(accepted.take(length - key.len()));
+    }
+    /*-----------REJECTION SAMPLING-----------*/
+    Ok(key)
+}
+
+/// Generates a key of `length` symbols as it is written to a key file: wrapped in a
+/// container if `container` is set, else bare with a trailing newline in text modes.
+pub fn generate_key_file(length: usize, mode: &Mode, container: bool) -> Result<Vec<u8>, OtpError> {
+    if container {
+        return Ok(KeyFile::generate(length, mode)?.to_bytes());
+    }
+    let mut key = generate_key(length, mode)?;
+    if !mode.is_binary() {
+        key.push(b'\n');
+    }
+    Ok(key)
+}
@@ -0,0 +1,327 @@
+/*!
+ * -----------------------------------------
+ * Pad ledger.
+ * Tracks how much of a key has been consumed so no part of
+ * the pad ever encrypts two messages. A key container's
+ * ledger is kept by its key ID in the ledger directory
+ * (`$OTP_LEDGER_DIR`, or `~/.otp/ledgers`), so copies and
+ * renames of the key share one ledger; a plain key's ledger
+ * lives next to it as `<key>.ledger`:
+ *
+ * ```text
+ * OTPLEDGER 1
+ * key=<key ID, for key containers>
+ * next=<first unused pad symbol>
+ * range=<start>-<end>
+ * ```
+ *
+ * with one `range` line per reservation. Reserving takes an
+ * exclusive lock on the ledger, so concurrent encryptions
+ * with the same key always receive disjoint ranges.
+ * Reserved ranges are never released, even if the session
+ * that reserved them fails.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::error::OtpError;
+use crate::frame::parse_fields;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const MAGIC: &str = "OTPLEDGER 1";
+const LEDGER_DIR_VAR: &str = "OTP_LEDGER_DIR";
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone)]
+pub struct PadLedger {
+    path: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum LedgerError {
+    Io(io::Error),
+    /// The ledger is unreadable or belongs to a different key.
+    Mismatch(String),
+    /// The range would reuse pad material already consumed.
+    Reuse(String),
+    /// Fewer unused symbols are left than the message needs.
+    Exhausted { available: u64, requested: u64 },
+}
+
+// Parsed contents of a ledger file.
+#[derive(Debug, Default)]
+struct Entries {
+    key: Option<String>,
+    next: u64,
+    ranges: Vec<Range<u64>>,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl PadLedger {
+    /// The ledger belonging to the key at `key_path`: by `key_id` in the ledger
+    /// directory for a container, next to the key for a plain key. A container
+    /// that still has a ledger next to it is refused rather than given a fresh one.
+    pub fn for_key(key_path: &str, key_id: Option<&str>) -> Result<Self, OtpError> {
+        let beside = PadLedger::beside(key_path);
+        let Some(key_id) = key_id else {
+            return Ok(beside);
+        };
+        let ledger = PadLedger { path: ledger_dir(key_path).join(format!("{}.ledger", key_id)).to_string_lossy().into_owned() };
+        if beside.exists() {
+            return Err(LedgerError::Mismatch(format!(
+                "{} is an older ledger kept next to the key; move it to {} to keep using it", beside.path, ledger.path)).into());
+        }
+        Ok(ledger)
+    }
+
+    /// The ledger kept next to the key at `key_path`, as `<key>.ledger`.
+    pub fn beside(key_path: &str) -> Self {
+        PadLedger { path: format!("{}.ledger", key_path) }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        Path::new(&self.path).exists()
+    }
+
+    /// Number of pad symbols consumed so far; 0 if there is no ledger yet.
+    pub fn consumed(&self, key_id: Option<&str>) -> Result<u64, OtpError> {
+        Ok(self.read(key_id)?.next)
+    }
+
+    /// Whether every symbol of `range` lies in ranges reserved before;
+    /// false if there is no ledger yet.
+    pub fn covers(&self, key_id: Option<&str>, range: &Range<u64>) -> Result<bool, OtpError> {
+        Ok(self.read(key_id)?.covers(range))
+    }
+
+    /// Atomically reserves the next `length` unused symbols of a pad of
+    /// `pad_length` symbols, creating the ledger if needed.
+    pub fn reserve(&self, key_id: Option<&str>, pad_length: u64, length: u64) -> Result<Range<u64>, OtpError> {
+        Ok(self.record(key_id, pad_length, None, length)?)
+    }
+
+    /// Atomically reserves `length` symbols starting at `offset`,
+    /// failing if any of them were reserved before.
+    pub fn reserve_at(&self, key_id: Option<&str>, pad_length: u64, offset: u64, length: u64) -> Result<Range<u64>, OtpError> {
+        Ok(self.record(key_id, pad_length, Some(offset), length)?)
+    }
+
+    // Reads the ledger under a shared lock; no ledger yet reads as empty.
+    fn read(&self, key_id: Option<&str>) -> Result<Entries, LedgerError> {
+        if !self.exists() {
+            return Ok(Entries::default());
+        }
+        let mut file = File::open(&self.path)?;
+        file.lock_shared()?;
+        self.load(&mut file, key_id)
+    }
+
+    fn record(&self, key_id: Option<&str>, pad_length: u64, offset: Option<u64>, length: u64) -> Result<Range<u64>, LedgerError> {
+        if let Some(dir) = Path::new(&self.path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path)?;
+        file.lock()?; // released when `file` is dropped
+
+        /*-----------CHECK RANGE-----------*/
+        let mut entries = self.load(&mut file, key_id)?;
+        let start = offset.unwrap_or(entries.next);
+        let available = pad_length.saturating_sub(start);
+        if length > available {
+            return Err(LedgerError::Exhausted { available, requested: length });
+        }
+        let range = start..start + length;
+        if let Some(used) = entries.ranges.iter().find(|used| used.start < range.end && range.start < used.end) {
+            return Err(LedgerError::Reuse(format!(
+                "Range {}-{} overlaps {}-{}, which is already consumed", range.start, range.end, used.start, used.end)));
+        }
+        /*-----------CHECK RANGE-----------*/
+
+        /*-----------RECORD RANGE-----------*/
+        entries.key = entries.key.or(key_id.map(str::to_string));
+        entries.next = entries.next.max(range.end);
+        entries.ranges.push(range.clone());
+        let contents = entries.to_string();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        /*-----------RECORD RANGE-----------*/
+
+        Ok(range)
+    }
+
+    // Reads the ledger through the locked `file`; an empty file is a new ledger.
+    fn load(&self, file: &mut File, key_id: Option<&str>) -> Result<Entries, LedgerError> {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .map_err(|_| LedgerError::Mismatch(format!("{} is not a pad ledger", self.path)))?;
+        if contents.is_empty() {
+            return Ok(Entries::default());
+        }
+        let entries = Entries::parse(&contents)
+            .ok_or_else(|| LedgerError::Mismatch(format!("{} is not a valid pad ledger", self.path)))?;
+        if let (Some(recorded), Some(key_id)) = (&entries.key, key_id) {
+            if recorded != key_id {
+                return Err(LedgerError::Mismatch(format!("{} belongs to key {}, not {}", self.path, recorded, key_id)));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// Where container ledgers are kept: `$OTP_LEDGER_DIR`, else `~/.otp/ledgers`,
+// else next to the key when there is no home directory.
+fn ledger_dir(key_path: &str) -> PathBuf {
+    if let Some(dir) = env::var_os(LEDGER_DIR_VAR).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    match env::var_os("HOME").filter(|home| !home.is_empty()) {
+        Some(home) => Path::new(&home).join(".otp").join("ledgers"),
+        None => Path::new(key_path).parent().map_or_else(PathBuf::new, Path::to_path_buf),
+    }
+}
+
+impl Entries {
+    fn parse(contents: &str) -> Option<Self> {
+        let fields = parse_fields(contents.strip_prefix(MAGIC)?.as_bytes())?;
+        let mut entries = Entries::default();
+        for (name, value) in fields {
+            match name {
+                "key" => entries.key = Some(value.to_string()),
+                "next" => entries.next = value.parse().ok()?,
+                "range" => {
+                    let (start, end) = value.split_once('-')?;
+                    entries.ranges.push(start.parse().ok()?..end.parse().ok()?);
+                }
+                _ => {} // unknown fields are ignored
+            }
+        }
+        // `next` must cover every recorded range
+        entries.ranges.iter().all(|range| range.end <= entries.next).then_some(entries)
+    }
+
+    // Walks the recorded ranges in order, extending the covered prefix of `range`
+    // for as long as they leave no gap.
+    fn covers(&self, range: &Range<u64>) -> bool {
+        let mut used = self.ranges.clone();
+        used.sort_by_key(|used| used.start);
+        let mut covered = range.start;
+        for used in used {
+            if covered >= range.end || used.start > covered {
+                break;
+            }
+            covered = covered.max(used.end);
+        }
+        covered >= range.end
+    }
+}
+
+impl fmt::Display for Entries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        if let Some(key) = &self.key {
+            writeln!(f, "key={}", key)?;
+        }
+        writeln!(f, "next={}", self.next)?;
+        for range in &self.ranges {
+            writeln!(f, "range={}-{}", range.start, range.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<io::Error> for LedgerError {
+    fn from(error: io::Error) -> Self {
+        LedgerError::Io(error)
+    }
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::Io(error) => write!(f, "Pad ledger error: {}", error),
+            LedgerError::Mismatch(message) | LedgerError::Reuse(message) => f.write_str(message),
+            LedgerError::Exhausted { available, requested } =>
+                write!(f, "Key has {} unused symbols left, message needs {}", available, requested),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    // A ledger in the temp directory, removed when the test ends.
+    struct TempLedger(PadLedger);
+
+    impl TempLedger {
+        fn new(name: &str) -> Self {
+            let key = env::temp_dir().join(format!("otp-ledger-test-{}-{}.key", process::id(), name));
+            let ledger = PadLedger::beside(&key.to_string_lossy());
+            let _ = fs::remove_file(ledger.path());
+            TempLedger(ledger)
+        }
+    }
+
+    impl Drop for TempLedger {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(self.0.path());
+        }
+    }
+
+    #[test]
+    fn reserve_hands_out_consecutive_ranges() {
+        let ledger = TempLedger::new("sequential");
+        assert_eq!(ledger.0.consumed(Some("k1")).unwrap(), 0);
+        assert_eq!(ledger.0.reserve(Some("k1"), 100, 5).unwrap(), 0..5);
+        assert_eq!(ledger.0.reserve(Some("k1"), 100, 3).unwrap(), 5..8);
+        assert_eq!(ledger.0.consumed(Some("k1")).unwrap(), 8);
+        assert!(ledger.0.covers(Some("k1"), &(2..8)).unwrap());
+        assert!(!ledger.0.covers(Some("k1"), &(6..9)).unwrap());
+    }
+
+    #[test]
+    fn reserve_at_rejects_overlaps() {
+        let ledger = TempLedger::new("overlap");
+        assert_eq!(ledger.0.reserve_at(Some("k1"), 100, 10, 5).unwrap(), 10..15);
+        assert!(matches!(ledger.0.reserve_at(Some("k1"), 100, 12, 5), Err(OtpError::KeyReuse(_))));
+        assert!(matches!(ledger.0.reserve_at(Some("k1"), 100, 5, 6), Err(OtpError::KeyReuse(_))));
+        assert_eq!(ledger.0.reserve_at(Some("k1"), 100, 15, 2).unwrap(), 15..17);
+        assert_eq!(ledger.0.reserve(Some("k1"), 100, 1).unwrap(), 17..18);
+    }
+
+    #[test]
+    fn reserve_fails_when_the_pad_runs_out() {
+        let ledger = TempLedger::new("exhausted");
+        assert_eq!(ledger.0.reserve(Some("k1"), 10, 8).unwrap(), 0..8);
+        assert!(matches!(ledger.0.reserve(Some("k1"), 10, 3), Err(OtpError::KeyTooShort(_))));
+        assert!(matches!(ledger.0.reserve_at(Some("k1"), 10, 9, 2), Err(OtpError::KeyTooShort(_))));
+        assert_eq!(ledger.0.reserve(Some("k1"), 10, 2).unwrap(), 8..10);
+        assert_eq!(ledger.0.consumed(Some("k1")).unwrap(), 10);
+    }
+
+    #[test]
+    fn ledger_refuses_a_different_key() {
+        let ledger = TempLedger::new("mismatch");
+        ledger.0.reserve(Some("k1"), 100, 4).unwrap();
+        assert!(matches!(ledger.0.reserve(Some("k2"), 100, 4), Err(OtpError::Input(_))));
+        assert!(matches!(ledger.0.consumed(Some("k2")), Err(OtpError::Input(_))));
+        assert_eq!(ledger.0.consumed(Some("k1")).unwrap(), 4);
+    }
+}
@@ -0,0 +1,76 @@
+/*!
+ * -----------------------------------------
+ * Cipher modes.
+ * Modular mode adds/subtracts symbol indices modulo the
+ * size of an alphabet and only accepts text in that alphabet.
+ * XOR mode is a byte-oriented Vernam cipher for arbitrary files.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt;
+use std::str::FromStr;
+use crate::alphabet::Alphabet;
+use crate::error::OtpError;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const XOR_DESCRIPTOR: &str = "xor";
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Modular(Alphabet),
+    Xor,
+}
+
+impl Mode {
+    /// The alphabet for modular mode, `None` in XOR mode.
+    pub fn alphabet(&self) -> Option<&Alphabet> {
+        match self {
+            Mode::Modular(alphabet) => Some(alphabet),
+            Mode::Xor => None,
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Mode::Xor)
+    }
+
+    /// Wire descriptor: the alphabet descriptor in modular mode, "xor" otherwise.
+    pub fn descriptor(&self) -> String {
+        match self {
+            Mode::Modular(alphabet) => alphabet.descriptor(),
+            Mode::Xor => XOR_DESCRIPTOR.to_string(),
+        }
+    }
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Modular(Alphabet::classic())
+    }
+}
+
+impl From<Alphabet> for Mode {
+    fn from(alphabet: Alphabet) -> Self {
+        Mode::Modular(alphabet)
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.descriptor())
+    }
+}
+
+impl FromStr for Mode {
+    type Err = OtpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            XOR_DESCRIPTOR => Ok(Mode::Xor),
+            _ => s.parse().map(Mode::Modular),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+/*!
+ * -----------------------------------------
+ * Address resolution for clients and servers.
+ * Hosts may be names, IPv4 literals, or IPv6 literals
+ * with or without brackets; names can resolve to several
+ * addresses, and each is tried in turn.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+/*-----------USE STATEMENTS-----------*/
+
+/// Resolves `host` and `port` to every matching socket address.
+pub fn resolve(host: &str, port: &str) -> io::Result<Vec<SocketAddr>> {
+    let port: u16 = port.parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid port '{}'", port)))?;
+    let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host); // "[::1]" -> "::1"
+    let addresses: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+    if addresses.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("No addresses found for '{}'", host)));
+    }
+    Ok(addresses)
+}
+
+/// Connects to the first address of `host` that accepts, returning the last error if none do.
+pub fn connect(host: &str, port: &str) -> io::Result<TcpStream> {
+    try_each(resolve(host, port)?, TcpStream::connect)
+}
+
+/// Like `connect`, giving each address at most `timeout` to accept.
+pub fn connect_timeout(host: &str, port: &str, timeout: Duration) -> io::Result<TcpStream> {
+    try_each(resolve(host, port)?, |address| TcpStream::connect_timeout(&address, timeout))
+}
+
+/// Binds the first address of `host` that can be bound.
+pub fn bind(host: &str, port: &str) -> io::Result<TcpListener> {
+    try_each(resolve(host, port)?, TcpListener::bind)
+}
+
+fn try_each<T>(addresses: Vec<SocketAddr>, mut attempt: impl FnMut(SocketAddr) -> io::Result<T>) -> io::Result<T> {
+    let mut last_error = None;
+    for address in addresses {
+        match attempt(address) {
+            Ok(value) => return Ok(value),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No addresses to try")))
+}
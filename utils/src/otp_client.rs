@@ -0,0 +1,348 @@
+/*!
+ * -----------------------------------------
+ * Client library for the one-time pad servers.
+ * `OtpClient` connects to a server and runs encrypt or
+ * decrypt sessions on it, returning the output as a value
+ * or streaming it to any writer. Data and key are sent in
+ * bounded chunks on one thread while output is received on
+ * another, so memory stays flat whatever their size.
+ * Sessions are bounded by the timeouts in the options.
+ * A server that supports batches keeps the connection open
+ * after each session, and the next one reuses it.
+ * The file-level client in utils::client is built on it.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::io::{self, Cursor, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread;
+use crate::alphabet::Alphabet;
+use crate::cipher::Direction;
+use crate::error::OtpError;
+use crate::frame::{read_frame, write_frame, Frame, FrameKind};
+use crate::handshake::{Hello, BATCH_FEATURE};
+use crate::input::read_chunk;
+use crate::mode::Mode;
+use crate::net::{connect, connect_timeout};
+use crate::server_error::ServerError;
+use crate::timeout::{Phase, SessionClock, Timeouts};
+use crate::{interleave_buffers, CHUNK_SIZE};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+/// How an `OtpClient` talks to its server.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    pub mode: Mode, // must match the server's
+    pub timeouts: Timeouts, // the handshake timeout also bounds connecting
+}
+
+/// A connection to an encryption or decryption server.
+pub struct OtpClient {
+    host: String,
+    port: String,
+    options: ClientOptions,
+    connection: Option<TcpStream>, // opened by `connect`, or kept from a batch session
+}
+
+/// What one session sends: data symbols, interleaved with key symbols unless
+/// the server supplies the key, checked against the alphabet as they are read.
+pub(crate) struct Payload<'a> {
+    pub(crate) data: Box<dyn Read + Send + 'a>,
+    pub(crate) key: Option<Box<dyn Read + Send + 'a>>,
+    pub(crate) alphabet: Option<&'a Alphabet>,
+    pub(crate) label: &'static str, // names the data in errors
+    pub(crate) expected: Option<u64>, // symbols the data must have, from an envelope
+    pub(crate) symbols: u64, // data symbols read so far
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl OtpClient {
+    /// Connects to the server at `addr`, given as `host:port`; IPv6 hosts go in brackets.
+    pub fn connect(addr: &str, options: ClientOptions) -> Result<Self, OtpError> {
+        let (host, port) = addr.rsplit_once(':')
+            .ok_or_else(|| OtpError::Input(format!("Expected host:port, got '{}'", addr)))?;
+        OtpClient::connect_to(host, port, options)
+    }
+
+    pub(crate) fn connect_to(host: &str, port: &str, options: ClientOptions) -> Result<Self, OtpError> {
+        let mut client = OtpClient { host: host.to_string(), port: port.to_string(), options, connection: None };
+        client.connection = Some(client.open()?);
+        Ok(client)
+    }
+
+    pub fn options(&self) -> &ClientOptions {
+        &self.options
+    }
+
+    /// Encrypts `plaintext` with `key`, which must be at least as long, and returns the ciphertext.
+    /// Both are used exactly as given: text-mode newlines are symbols like any other.
+    pub fn encrypt(&mut self, plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>, OtpError> {
+        let mut ciphertext = Vec::with_capacity(plaintext.len());
+        self.encrypt_reader(plaintext, key, &mut ciphertext)?;
+        Ok(ciphertext)
+    }
+
+    /// Decrypts `ciphertext` with `key` and returns the plaintext.
+    pub fn decrypt(&mut self, ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>, OtpError> {
+        let mut plaintext = Vec::with_capacity(ciphertext.len());
+        self.decrypt_reader(ciphertext, key, &mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    /// Encrypts everything `plaintext` yields with `key`, writing the ciphertext to `out`
+    /// as it arrives. Returns the number of symbols encrypted. On an error, `out` may
+    /// already hold the output for the symbols before it.
+    pub fn encrypt_reader<R, K, W>(&mut self, plaintext: R, key: K, out: &mut W) -> Result<u64, OtpError>
+    where
+        R: Read + Send,
+        K: Read + Send,
+        W: Write,
+    {
+        self.cipher_reader(Direction::Encrypt, Box::new(plaintext), Box::new(key), out)
+    }
+
+    /// Decrypts everything `ciphertext` yields with `key`, writing the plaintext to `out`
+    /// as it arrives. Returns the number of symbols decrypted.
+    pub fn decrypt_reader<R, K, W>(&mut self, ciphertext: R, key: K, out: &mut W) -> Result<u64, OtpError>
+    where
+        R: Read + Send,
+        K: Read + Send,
+        W: Write,
+    {
+        self.cipher_reader(Direction::Decrypt, Box::new(ciphertext), Box::new(key), out)
+    }
+
+    fn cipher_reader<W: Write>(
+        &mut self,
+        operation: Direction,
+        data: Box<dyn Read + Send + '_>,
+        key: Box<dyn Read + Send + '_>,
+        out: &mut W,
+    ) -> Result<u64, OtpError> {
+        let mode = self.options.mode.clone();
+        let label = match operation {
+            Direction::Encrypt => "Plaintext",
+            Direction::Decrypt => "Ciphertext",
+        };
+        let mut payload = Payload { data, key: Some(key), alphabet: mode.alphabet(), label, expected: None, symbols: 0 };
+        let symbols = self.run(&Hello::new(operation, mode.clone()), &mut payload, out, |_, _, _| Ok(()))?;
+        out.flush().map_err(|e| OtpError::Io("Error writing output".to_string(), e))?;
+        Ok(symbols)
+    }
+
+    /// Runs one session: sends `hello`, passes the server's reply to `accepted` before
+    /// any output is written, where the payload's key may still be filled in, then sends
+    /// the payload. Returns the symbols processed.
+    /// The session asks for the batch feature, and keeps the connection if the server grants it.
+    pub(crate) fn run<'p, W, F>(&mut self, hello: &Hello, payload: &mut Payload<'p>, out: &mut W, accepted: F) -> Result<u64, OtpError>
+    where
+        W: Write,
+        F: FnOnce(&Hello, &mut Payload<'p>, &mut W) -> Result<(), OtpError>,
+    {
+        let clock = self.options.timeouts.start();
+        let held = match self.connection.take() {
+            Some(stream) => still_open(stream, &clock)?,
+            None => None,
+        };
+        let fresh = held.is_none();
+        let stream = match held {
+            Some(stream) => stream,
+            None => self.open()?,
+        };
+        let request = hello.clone().with_feature(BATCH_FEATURE);
+        let reply = match client_handshake(&mut clock.timed(&stream, Phase::Handshake), &request) {
+            Ok(reply) => reply,
+            // the server may have closed a connection held since `connect` or the last session; try a new one
+            Err(e) if !fresh && closed_before_reply(&e) => return self.run(hello, payload, out, accepted),
+            Err(e) => return Err(e),
+        };
+        accepted(&reply, payload, out)?;
+        let symbols = exchange(&stream, &clock, payload, out)?;
+        if reply.has_feature(BATCH_FEATURE) {
+            self.connection = Some(stream);
+        }
+        Ok(symbols)
+    }
+
+    // Opens a connection, giving up after the handshake timeout.
+    fn open(&self) -> Result<TcpStream, OtpError> {
+        let stream = match self.options.timeouts.handshake {
+            Some(timeout) => connect_timeout(&self.host, &self.port, timeout),
+            None => connect(&self.host, &self.port),
+        };
+        stream.map_err(|e| OtpError::Connection("Failed to connect to server".to_string(), e))
+    }
+}
+
+// Checks a held connection before sending a HELLO on it. Returns it if it is still
+// waiting, or `None` if the server has closed it. A frame the server sent meanwhile,
+// such as `busy` or `timeout`, is read and returned as the error.
+fn still_open(stream: TcpStream, clock: &SessionClock) -> Result<Option<TcpStream>, OtpError> {
+    let failed = |e| OtpError::session("Handshake failed", e);
+    stream.set_nonblocking(true).map_err(failed)?;
+    let peeked = stream.peek(&mut [0; 1]);
+    stream.set_nonblocking(false).map_err(failed)?;
+    match peeked {
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Some(stream)),
+        Ok(0) => Ok(None),
+        Err(e) if matches!(e.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted) => Ok(None),
+        Err(e) => Err(failed(e)),
+        Ok(_) => {
+            let frame = read_frame(&mut clock.timed(&stream, Phase::Handshake)).map_err(failed)?;
+            match frame.kind {
+                FrameKind::Error => Err(OtpError::server("Handshake failed", ServerError::from_frame(&frame))),
+                _ => Err(OtpError::Protocol("Handshake failed: Server sent a frame before the HELLO".to_string())),
+            }
+        }
+    }
+}
+
+// Whether a handshake failed only because the connection was already closed, before
+// the server answered at all. Anything the server said, a rejection or a timeout,
+// stands: sending the HELLO again would repeat its side effects, such as reserving a pad range.
+fn closed_before_reply(error: &OtpError) -> bool {
+    matches!(error, OtpError::Connection(_, e) if matches!(
+        e.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe
+    ))
+}
+
+/// Sends the client's HELLO and returns what the server accepted.
+pub fn client_handshake<S: Read + Write>(stream: &mut S, hello: &Hello) -> Result<Hello, OtpError> {
+    let failed = |e| OtpError::session("Handshake failed", e);
+    write_frame(stream, &hello.to_frame()).map_err(failed)?;
+
+    let response = read_frame(stream).map_err(failed)?;
+    match response.kind {
+        FrameKind::Hello => {
+            let accepted = Hello::from_frame(&response)
+                .map_err(|r| OtpError::Protocol(format!("Handshake failed: {}", r)))?;
+            if accepted.version > hello.version || accepted.operation != hello.operation || accepted.mode != hello.mode {
+                return Err(OtpError::HandshakeMismatch("Handshake failed: Server accepted a different session than requested".to_string()));
+            }
+            Ok(accepted)
+        }
+        FrameKind::Error => Err(OtpError::server("Handshake failed", ServerError::from_frame(&response))),
+        _ => Err(OtpError::Protocol("Handshake failed: Server answered HELLO with an unexpected frame".to_string())),
+    }
+}
+
+/// Sends the interleaved buffer as DATA frames followed by END,
+/// writing each output byte to `out` as soon as the server returns it.
+/// Each frame is bounded by the idle timeout, and the exchange by the session timeout.
+pub fn send_and_receive<W: Write>(stream: &TcpStream, interleaved_buffer: &[u8], out: &mut W, trailing_newline: bool, timeouts: Timeouts) -> Result<(), OtpError> {
+    let data: Vec<u8> = interleaved_buffer.iter().step_by(2).copied().collect();
+    let key: Vec<u8> = interleaved_buffer.iter().skip(1).step_by(2).copied().collect();
+    let mut payload = Payload {
+        data: Box::new(Cursor::new(data)),
+        key: Some(Box::new(Cursor::new(key))),
+        alphabet: None, // left to the server
+        label: "Data",
+        expected: None,
+        symbols: 0,
+    };
+    exchange(stream, &timeouts.start(), &mut payload, out)?;
+    let written = if trailing_newline { out.write_all(b"\n") } else { Ok(()) };
+    written.and_then(|_| out.flush()).map_err(|e| OtpError::Io("Error writing output".to_string(), e))
+}
+
+impl Payload<'_> {
+    /// Fills `chunk` with the next frame's worth of payload, returning false at the end of the data.
+    pub(crate) fn next_chunk(&mut self, chunk: &mut Vec<u8>) -> Result<bool, OtpError> {
+        let symbols = if self.key.is_some() { CHUNK_SIZE / 2 } else { CHUNK_SIZE }; // per frame
+        let mut data = [0u8; CHUNK_SIZE];
+        let n = read_chunk(&mut self.data, &mut data[..symbols])
+            .map_err(|e| OtpError::Io(format!("Error reading {}", self.label.to_lowercase()), e))?;
+        chunk.clear();
+
+        if n == 0 {
+            return match self.expected.filter(|&expected| expected != self.symbols) {
+                Some(expected) => Err(OtpError::Input(format!(
+                    "Error reading ciphertext file: envelope says {} symbols, found {}", expected, self.symbols))),
+                None => Ok(false),
+            };
+        }
+        let data = &data[..n];
+        if let Some(alphabet) = self.alphabet {
+            alphabet.validate_at(data, self.symbols)
+                .map_err(|e| e.context(&format!("{} contains invalid characters", self.label)))?;
+        }
+
+        match &mut self.key {
+            Some(key) => {
+                let mut key_chunk = [0u8; CHUNK_SIZE / 2];
+                let key_chunk = &mut key_chunk[..n];
+                let k = read_chunk(key, key_chunk).map_err(|e| OtpError::Io("Error reading key".to_string(), e))?;
+                if k < n {
+                    return Err(OtpError::KeyTooShort("Error: Key is too short".to_string()));
+                }
+                chunk.extend_from_slice(&interleave_buffers(data, key_chunk));
+            }
+            None => chunk.extend_from_slice(data),
+        }
+        self.symbols += n as u64;
+        Ok(true)
+    }
+}
+
+// Streams the payload to the server on one thread while writing the server's output
+// to `out` on this one, so neither side waits for the other to finish. Returns the
+// symbols processed.
+fn exchange<W: Write>(stream: &TcpStream, clock: &SessionClock, payload: &mut Payload, out: &mut W) -> Result<u64, OtpError> {
+    let (sent, received) = thread::scope(|scope| {
+        let sender = scope.spawn(|| {
+            let sent = send_payload(stream, clock, payload);
+            if sent.is_err() {
+                let _ = stream.shutdown(Shutdown::Both); // nothing more will follow; unblock the receiver
+            }
+            sent
+        });
+        let received = receive_output(stream, clock, out);
+        if received.is_err() {
+            let _ = stream.shutdown(Shutdown::Both); // the server is done with us; stop sending
+        }
+        (sender.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)), received)
+    });
+
+    match (sent, received) {
+        // bad input cut the session short, so the receiver's error is only a consequence
+        (Err(e @ (OtpError::Input(_) | OtpError::Io(..) | OtpError::InvalidSymbol { .. } | OtpError::KeyTooShort(_))), _) => Err(e),
+        (_, Err(e)) => Err(e),
+        (Err(e), Ok(_)) => Err(e),
+        (Ok(sent), Ok(received)) if sent != received => Err(OtpError::Protocol(format!(
+            "Communication error: Server returned {} symbols for the {} sent", received, sent))),
+        (Ok(sent), Ok(_)) => Ok(sent),
+    }
+}
+
+// Sends the payload as DATA frames followed by END, returning the data symbols sent.
+fn send_payload(stream: &TcpStream, clock: &SessionClock, payload: &mut Payload) -> Result<u64, OtpError> {
+    let send = |frame: &Frame| write_frame(&mut clock.timed(stream, Phase::Idle), frame)
+        .map_err(|e| OtpError::session("Communication error", e));
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    while payload.next_chunk(&mut chunk)? {
+        send(&Frame::data(&chunk))?;
+    }
+    send(&Frame::end())?;
+    Ok(payload.symbols)
+}
+
+// Writes every DATA frame from the server to `out` until END, returning the bytes written.
+fn receive_output<W: Write>(stream: &TcpStream, clock: &SessionClock, out: &mut W) -> Result<u64, OtpError> {
+    let mut received = 0;
+    loop {
+        let frame = read_frame(&mut clock.timed(stream, Phase::Idle))
+            .map_err(|e| OtpError::session("Communication error", e))?;
+        match frame.kind {
+            FrameKind::Data => {
+                out.write_all(&frame.payload).map_err(|e| OtpError::Io("Error writing output".to_string(), e))?;
+                received += frame.payload.len() as u64;
+            }
+            FrameKind::End => return Ok(received),
+            FrameKind::Error => return Err(OtpError::server("Communication error", ServerError::from_frame(&frame))),
+            FrameKind::Hello => return Err(OtpError::Protocol("Communication error: Unexpected HELLO frame".to_string())),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+/*!
+ * -----------------------------------------
+ * Output sinks for the clients and keygen.
+ * Output goes to stdout, or to a file that is written
+ * under a temporary name next to it and renamed into
+ * place only once everything was written, so a session
+ * that fails part way never leaves partial output
+ * under the name that was asked for.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fs::{self, File};
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+/// Where output is written. Call `commit` once it is complete; a file
+/// dropped without being committed is removed.
+pub enum Output {
+    Stdout(StdoutLock<'static>),
+    File(AtomicFile),
+}
+
+/// A file written under a temporary name in the same directory,
+/// then renamed over `path` by `commit`.
+pub struct AtomicFile {
+    writer: BufWriter<File>,
+    temp: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+/// Opens the output sink: the named file, or stdout when there is no path.
+pub fn open_output(path: Option<&str>) -> io::Result<Output> {
+    match path {
+        Some(path) => Ok(Output::File(AtomicFile::create(path)?)),
+        None => Ok(Output::Stdout(io::stdout().lock())),
+    }
+}
+
+impl Output {
+    /// Flushes the output and, for a file, moves it into place.
+    pub fn commit(self) -> io::Result<()> {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush(),
+            Output::File(file) => file.commit(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File(file) => file.writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.writer.flush(),
+        }
+    }
+}
+
+impl AtomicFile {
+    /// Creates the temporary file for `path`; nothing appears under `path` yet.
+    pub fn create(path: &str) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        let name = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a file name", path.display())))?;
+        // the same directory keeps the rename on one filesystem, where it is atomic
+        let temp = path.parent().unwrap_or(Path::new(""))
+            .join(format!(".{}.{}.tmp", name.to_string_lossy(), process::id()));
+        let file = File::options().write(true).create_new(true).open(&temp)?;
+        Ok(AtomicFile { writer: BufWriter::new(file), temp, path, committed: false })
+    }
+
+    /// Writes out everything buffered and renames the file over `path`.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        fs::rename(&self.temp, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
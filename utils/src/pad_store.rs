@@ -0,0 +1,96 @@
+/*!
+ * -----------------------------------------
+ * Pads held by the server.
+ * A pad directory holds key containers named `<key ID>.key`.
+ * Their ledgers are the ones clients use for the same key
+ * ID (see ledger.rs). Encryption reserves a range in the
+ * ledger, either the next unused one or the offset the
+ * client asked for; decryption is only served for ranges
+ * the ledger shows were used, so unused pad can't be read
+ * back by decrypting known data. Either way the key material
+ * never crosses the network, and the session's range is
+ * read from disk a frame at a time.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fs::File;
+use std::io::Take;
+use std::path::PathBuf;
+use crate::cipher::Direction;
+use crate::error::OtpError;
+use crate::handshake::PadRef;
+use crate::key_file::KeyFile;
+use crate::ledger::PadLedger;
+use crate::mode::Mode;
+use crate::server_error::{ErrorCode, ServerError};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone)]
+pub struct PadStore {
+    dir: PathBuf,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl PadStore {
+    pub fn new(dir: &str) -> Self {
+        PadStore { dir: PathBuf::from(dir) }
+    }
+
+    /// Opens the pad range for one session. Returns the range's offset
+    /// and a reader over its key material.
+    pub fn open(&self, pad: &PadRef, operation: Direction, mode: &Mode) -> Result<(u64, Take<File>), ServerError> {
+        /*-----------LOAD PAD-----------*/
+        // IDs are plain hex, so they can't name anything outside the directory
+        if pad.key_id.is_empty() || !pad.key_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ServerError::new(ErrorCode::UnknownKey, &format!("Malformed key ID '{}'", pad.key_id)));
+        }
+        let path = self.dir.join(format!("{}.key", pad.key_id)).to_string_lossy().into_owned();
+        let unusable = |e: OtpError| ServerError::new(ErrorCode::UnknownKey, &format!("No usable pad {}: {}", pad.key_id, e));
+        let (header, _) = KeyFile::open(&path).map_err(unusable)?;
+        let pad_length = match &header {
+            Some(header) if header.id == pad.key_id && header.mode == *mode => header.length,
+            Some(header) if header.id == pad.key_id =>
+                return Err(ServerError::new(ErrorCode::UnknownKey, &format!("Pad {} is for mode '{}'", pad.key_id, header.mode))),
+            _ => return Err(ServerError::new(ErrorCode::UnknownKey, &format!("{} is not the container for pad {}", path, pad.key_id))),
+        };
+        // the same ledger clients reserve from when they hold a copy of the container
+        let ledger = PadLedger::for_key(&path, Some(&pad.key_id)).map_err(unusable)?;
+        /*-----------LOAD PAD-----------*/
+
+        /*-----------PICK RANGE-----------*/
+        let range = match (operation, pad.offset) {
+            (Direction::Encrypt, offset) => {
+                let reserved = match offset {
+                    Some(offset) => ledger.reserve_at(Some(&pad.key_id), pad_length, offset, pad.length),
+                    None => ledger.reserve(Some(&pad.key_id), pad_length, pad.length),
+                };
+                reserved.map_err(|e| match e {
+                    OtpError::KeyReuse(message) => ServerError::new(ErrorCode::KeyReuse, &message),
+                    OtpError::KeyTooShort(message) => ServerError::new(ErrorCode::KeyTooShort, &message),
+                    e => ServerError::new(ErrorCode::Internal, &e.to_string()),
+                })?
+            }
+            (Direction::Decrypt, Some(offset)) => {
+                if offset.saturating_add(pad.length) > pad_length {
+                    return Err(ServerError::new(ErrorCode::KeyTooShort, &format!("Pad {} has only {} symbols", pad.key_id, pad_length)));
+                }
+                let range = offset..offset + pad.length;
+                let used = ledger.covers(Some(&pad.key_id), &range)
+                    .map_err(|e| ServerError::new(ErrorCode::Internal, &e.to_string()))?;
+                if !used {
+                    return Err(ServerError::new(ErrorCode::UnknownKey, &format!(
+                        "Range {}-{} of pad {} was never used to encrypt, so there is nothing to decrypt", range.start, range.end, pad.key_id)));
+                }
+                range
+            }
+            (Direction::Decrypt, None) =>
+                return Err(ServerError::new(ErrorCode::Protocol, "Decrypting with a server pad needs the pad offset")),
+        };
+        /*-----------PICK RANGE-----------*/
+
+        let material = KeyFile::open_range(&path, range.clone()).map_err(unusable)?;
+        Ok((range.start, material))
+    }
+}
@@ -0,0 +1,227 @@
+/*!
+ * -----------------------------------------
+ * Fixed-size worker pool for the threaded server.
+ * A set number of workers take connections from a bounded
+ * queue. When every worker is busy and the queue is full,
+ * the queue policy decides what happens to a new connection:
+ * wait for room (block), turn it away (reject), or turn
+ * away the connection that has waited longest (drop-oldest).
+ * Queue depth and counters are kept for sizing the pool.
+ * Closing the pool hands back the queued jobs and lets
+ * the caller wait for the running ones.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+use crate::error::OtpError;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+const STOP_POLL: Duration = Duration::from_millis(100); // how often a blocked submit checks whether to give up
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+/// What to do with a new job when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePolicy {
+    Block,
+    Reject,
+    DropOldest,
+}
+
+/// A snapshot of the pool's load and what it has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub workers: usize,
+    pub busy: usize,
+    pub backlog: usize, // queue capacity
+    pub queued: usize,
+    pub peak_queued: usize,
+    pub served: u64,
+    pub rejected: u64,
+    pub dropped: u64,
+}
+
+/// Outcome of handing a job to the pool.
+pub(crate) enum Submitted<T> {
+    Queued,
+    /// The queue was full, the pool closed or the submitter stopped waiting; the job was not taken.
+    Rejected(T),
+    /// The job was queued in place of this one, which had waited longest.
+    Displaced(T),
+}
+
+pub(crate) struct WorkerPool<T> {
+    shared: Arc<Shared<T>>,
+    policy: QueuePolicy,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    work: Condvar,  // signalled when a job is queued
+    space: Condvar, // signalled when a job leaves the queue
+    idle: Condvar,  // signalled when a job finishes
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    stats: PoolStats,
+    closed: bool,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl FromStr for QueuePolicy {
+    type Err = OtpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(QueuePolicy::Block),
+            "reject" => Ok(QueuePolicy::Reject),
+            "drop-oldest" => Ok(QueuePolicy::DropOldest),
+            _ => Err(OtpError::Input(format!("Unknown queue policy '{}' (expected block, reject or drop-oldest)", s))),
+        }
+    }
+}
+
+impl fmt::Display for QueuePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            QueuePolicy::Block => "block",
+            QueuePolicy::Reject => "reject",
+            QueuePolicy::DropOldest => "drop-oldest",
+        })
+    }
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} workers busy, {}/{} queued (peak {}), {} served, {} rejected, {} dropped",
+            self.busy, self.workers, self.queued, self.backlog, self.peak_queued, self.served, self.rejected, self.dropped
+        )
+    }
+}
+
+impl<T: Send + 'static> WorkerPool<T> {
+    /// Starts `workers` threads running `handler` on each job, queueing at most `backlog` jobs.
+    pub(crate) fn new<F>(workers: usize, backlog: usize, policy: QueuePolicy, handler: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let stats = PoolStats { workers, backlog, ..PoolStats::default() };
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { queue: VecDeque::with_capacity(backlog), stats, closed: false }),
+            work: Condvar::new(),
+            space: Condvar::new(),
+            idle: Condvar::new(),
+        });
+        let handler = Arc::new(handler);
+        for _ in 0..workers {
+            let shared = Arc::clone(&shared);
+            let handler = Arc::clone(&handler);
+            spawn(move || shared.work_loop(&*handler));
+        }
+        WorkerPool { shared, policy }
+    }
+
+    /// Queues a job, applying the queue policy if the queue is full.
+    /// Under the block policy, gives up once `stop` returns true.
+    pub(crate) fn submit(&self, job: T, stop: impl Fn() -> bool) -> Submitted<T> {
+        let mut state = self.shared.lock();
+        let mut outcome = Submitted::Queued;
+        if state.queue.len() >= state.stats.backlog {
+            match self.policy {
+                QueuePolicy::Block => {
+                    while state.queue.len() >= state.stats.backlog && !state.closed {
+                        if stop() {
+                            return Submitted::Rejected(job);
+                        }
+                        state = self.shared.space.wait_timeout(state, STOP_POLL).unwrap_or_else(|e| e.into_inner()).0;
+                    }
+                }
+                QueuePolicy::Reject => {
+                    state.stats.rejected += 1;
+                    return Submitted::Rejected(job);
+                }
+                QueuePolicy::DropOldest => {
+                    if let Some(oldest) = state.queue.pop_front() {
+                        state.stats.dropped += 1;
+                        outcome = Submitted::Displaced(oldest);
+                    }
+                }
+            }
+        }
+        if state.closed {
+            return Submitted::Rejected(job);
+        }
+        state.queue.push_back(job);
+        state.stats.queued = state.queue.len();
+        state.stats.peak_queued = state.stats.peak_queued.max(state.stats.queued);
+        self.shared.work.notify_one();
+        outcome
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        self.shared.lock().stats
+    }
+
+    /// Stops taking jobs and returns the ones still queued; running jobs carry on.
+    pub(crate) fn close(&self) -> Vec<T> {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        let queued = state.queue.drain(..).collect();
+        state.stats.queued = 0;
+        self.shared.space.notify_all(); // wakes a submit blocked on a full queue
+        queued
+    }
+
+    /// Waits up to `timeout` for the running jobs to finish, returning the stats at the end.
+    pub(crate) fn wait_idle(&self, timeout: Duration) -> PoolStats {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        while state.stats.busy > 0 {
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else { break };
+            state = self.shared.idle.wait_timeout(state, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        state.stats
+    }
+}
+
+impl<T> Shared<T> {
+    // A panicking job can't leave the queue half-updated, so a poisoned lock is still usable.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn work_loop(&self, handler: &dyn Fn(T)) {
+        loop {
+            /*-----------TAKE NEXT JOB-----------*/
+            let mut state = self.lock();
+            let job = loop {
+                match state.queue.pop_front() {
+                    Some(job) => break job,
+                    None => state = self.work.wait(state).unwrap_or_else(|e| e.into_inner()),
+                }
+            };
+            state.stats.queued = state.queue.len();
+            state.stats.busy += 1;
+            self.space.notify_one();
+            drop(state);
+            /*-----------TAKE NEXT JOB-----------*/
+
+            handler(job);
+
+            let mut state = self.lock();
+            state.stats.busy -= 1;
+            state.stats.served += 1;
+            self.idle.notify_all();
+        }
+    }
+}
@@ -0,0 +1,299 @@
+/*!
+ * -----------------------------------------
+ * One-time pad server.
+ * Accepts connections on a port and serves each one on a
+ * worker from a fixed pool (see pool): handshake, then DATA frames of interleaved
+ * data/key bytes in, DATA frames of output back, until END.
+ * The operations a listener accepts are configurable, so one
+ * process can serve encryption, decryption, or both.
+ * Sessions are bounded by handshake, idle and session
+ * timeouts; see timeout.
+ * With a pad directory, clients may instead send only data
+ * and name a pad the server holds; see pad_store.
+ * SIGTERM or SIGINT shuts the server down gracefully; see
+ * shutdown. Builds with the `async` feature serve through
+ * async_server.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::io::{self, Read};
+use std::net::{Shutdown, TcpStream};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+use crate::args::Args;
+use crate::cipher::Direction;
+use crate::error::OtpError;
+use crate::frame::{read_frame, write_frame};
+use crate::mode::Mode;
+use crate::net::bind;
+use crate::pad_store::PadStore;
+use crate::pool::{QueuePolicy, Submitted, WorkerPool};
+use crate::server_error::{ErrorCode, ServerError};
+use crate::session::{Session, Step};
+use crate::shutdown::{DrainSummary, ShutdownFlag, DEFAULT_GRACE_PERIOD, SHUTDOWN_MESSAGE};
+use crate::timeout::{Phase, Timeouts};
+use crate::{server_error, CHUNK_SIZE};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub const DEFAULT_BIND: &str = "0.0.0.0";
+pub const DEFAULT_MAX_SESSIONS: usize = 256;
+pub const DEFAULT_WORKERS: usize = 16;
+pub const DEFAULT_BACKLOG: usize = 64;
+pub(crate) const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+const ACCEPT_POLL: Duration = Duration::from_millis(100); // how often the accept loop checks for shutdown
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub bind: String, // address to listen on
+    pub port: String,
+    pub mode: Mode,
+    pub operations: Vec<Direction>,
+    pub pads: Option<PadStore>, // pads held for the server-pad feature
+    pub max_sessions: usize, // concurrent session cap of the async server
+    pub workers: usize, // worker threads of the threaded server
+    pub backlog: usize, // connections queued for a free worker
+    pub policy: QueuePolicy, // what to do with connections once the backlog is full
+    pub stats_interval: Option<Duration>, // how often to print queue depth and counters
+    pub timeouts: Timeouts,
+    pub grace_period: Duration, // how long active sessions get to finish on shutdown
+}
+
+impl ServerConfig {
+    pub fn new(port: &str, mode: Mode, operations: &[Direction]) -> Self {
+        ServerConfig {
+            bind: DEFAULT_BIND.to_string(),
+            port: port.to_string(),
+            mode,
+            operations: operations.to_vec(),
+            pads: None,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            workers: DEFAULT_WORKERS,
+            backlog: DEFAULT_BACKLOG,
+            policy: QueuePolicy::Block,
+            stats_interval: None,
+            timeouts: Timeouts::default(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        }
+    }
+
+    /// Builds a config from `port [mode] [--bind ADDRESS] [--pad-dir DIR] [--grace-period SECONDS]` and the
+    /// timeout options of `Timeouts::from_args`, serving `operations`.
+    /// Async builds also take `--max-sessions N`; threaded builds take `--workers N`,
+    /// `--backlog N`, `--queue-policy block|reject|drop-oldest` and `--stats SECONDS`.
+    pub fn from_args(mut args: Args, operations: &[Direction]) -> Result<Self, OtpError> {
+        let mut config = ServerConfig::new("", Mode::default(), operations);
+        if let Some(bind) = args.value("--bind")? {
+            config.bind = bind;
+        }
+        config.pads = args.value("--pad-dir")?.map(|dir| PadStore::new(&dir));
+        config.timeouts = Timeouts::from_args(&mut args)?;
+        if let Some(seconds) = args.parsed::<u64>("--grace-period")? {
+            config.grace_period = Duration::from_secs(seconds);
+        }
+        #[cfg(feature = "async")]
+        {
+            config.max_sessions = positive(&mut args, "--max-sessions")?.unwrap_or(DEFAULT_MAX_SESSIONS);
+        }
+        #[cfg(not(feature = "async"))]
+        {
+            config.workers = positive(&mut args, "--workers")?.unwrap_or(DEFAULT_WORKERS);
+            config.backlog = positive(&mut args, "--backlog")?.unwrap_or(DEFAULT_BACKLOG);
+            config.policy = args.parsed("--queue-policy")?.unwrap_or(QueuePolicy::Block);
+            config.stats_interval = positive(&mut args, "--stats")?.map(|seconds| Duration::from_secs(seconds as u64));
+        }
+        let positionals = args.positionals()?;
+        match positionals.as_slice() {
+            [port] => config.port = port.clone(),
+            [port, mode] => (config.port, config.mode) = (port.clone(), mode.parse()?),
+            _ => return Err(OtpError::Input("Expected a port and an optional mode".to_string())),
+        }
+        Ok(config)
+    }
+}
+
+// Parses a numeric option that must be at least 1.
+fn positive(args: &mut Args, name: &str) -> Result<Option<usize>, OtpError> {
+    match args.parsed::<usize>(name)? {
+        Some(0) => Err(OtpError::Input(format!("{} must be at least 1", name))),
+        value => Ok(value),
+    }
+}
+
+/// Binds the configured port and serves connections until SIGTERM or SIGINT,
+/// on the async server when the `async` feature is enabled.
+pub fn run_server(config: ServerConfig) -> Result<(), OtpError> {
+    #[cfg(feature = "async")]
+    return crate::async_server::run_async_server(config);
+    #[cfg(not(feature = "async"))]
+    run_threaded_server(config)
+}
+
+/// Serves connections on a fixed pool of worker threads until SIGTERM or SIGINT,
+/// then drains the active sessions and prints a summary.
+pub fn run_threaded_server(config: ServerConfig) -> Result<(), OtpError> {
+    let shutdown = ShutdownFlag::register().map_err(|e| OtpError::Io("Failed to install signal handlers".to_string(), e))?;
+    let bind_failed = |e| OtpError::Connection(format!("Failed to bind {}:{}", config.bind, config.port), e);
+    let listener = bind(&config.bind, &config.port).map_err(bind_failed)?;
+    listener.set_nonblocking(true).map_err(bind_failed)?; // so the accept loop can notice a shutdown
+    println!(
+        "Server listening on {} ({} workers, backlog {}, {} when full)",
+        listener.local_addr().map_err(bind_failed)?, config.workers, config.backlog, config.policy
+    );
+    let config = Arc::new(config);
+    let pool = {
+        let config = Arc::clone(&config);
+        Arc::new(WorkerPool::new(config.workers, config.backlog, config.policy, move |stream| handle_client(stream, &config)))
+    };
+
+    /*-----------REPORT POOL STATS-----------*/
+    if let Some(interval) = config.stats_interval {
+        let pool = Arc::clone(&pool);
+        spawn(move || loop {
+            sleep(interval);
+            println!("Pool: {}", pool.stats());
+        });
+    }
+    /*-----------REPORT POOL STATS-----------*/
+
+    let full = ServerError::new(ErrorCode::Busy, "Server is at its session limit, try again later");
+    let mut stranded = None; // a client still waiting for room in the queue when the shutdown came
+    while !shutdown.requested() {
+        let stream = match listener.accept().and_then(|(stream, _)| stream.set_nonblocking(false).map(|_| stream)) {
+            Ok(stream) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                sleep(ACCEPT_POLL);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        match pool.submit(stream, || shutdown.requested()) {
+            Submitted::Queued => {}
+            Submitted::Rejected(stream) if shutdown.requested() => stranded = Some(stream),
+            Submitted::Rejected(stream) => {
+                println!("Rejecting client: pool is full ({})", pool.stats());
+                turn_away(stream, &full);
+            }
+            Submitted::Displaced(stream) => {
+                println!("Dropping oldest queued client: pool is full ({})", pool.stats());
+                turn_away(stream, &full);
+            }
+        }
+    }
+    drop(listener);
+
+    /*-----------DRAIN SESSIONS-----------*/
+    let mut queued = pool.close();
+    queued.extend(stranded);
+    let active = pool.stats().busy;
+    println!(
+        "Shutting down: waiting up to {}s for {} active sessions, turning away {} queued clients",
+        config.grace_period.as_secs(), active, queued.len()
+    );
+    let turned_away = queued.len();
+    let turning_away: Vec<_> = queued.into_iter() // in parallel, as each can take up to DRAIN_TIMEOUT
+        .map(|stream| spawn(move || turn_away(stream, &ServerError::new(ErrorCode::Busy, SHUTDOWN_MESSAGE))))
+        .collect();
+    let remaining = pool.wait_idle(config.grace_period).busy;
+    for thread in turning_away {
+        let _ = thread.join();
+    }
+    println!("Shutdown complete: {}", DrainSummary::new(active, remaining, turned_away));
+    /*-----------DRAIN SESSIONS-----------*/
+    Ok(())
+}
+
+// Sends `error` to a client that won't be served. Rejections run on the accept loop,
+// which is fine: a well-behaved client hangs up as soon as it reads the error.
+fn turn_away(mut stream: TcpStream, error: &ServerError) {
+    let _ = write_frame(&mut stream, &error.to_frame());
+    drain_and_close(&mut stream);
+}
+
+pub fn handle_dec_client(stream: TcpStream, mode: Mode) {
+    handle_client(stream, &ServerConfig::new("", mode, &[Direction::Decrypt]));
+}
+
+pub fn handle_enc_client(stream: TcpStream, mode: Mode) {
+    handle_client(stream, &ServerConfig::new("", mode, &[Direction::Encrypt]));
+}
+
+/// Serves one connection, accepting any of the configured operations in the handshake.
+pub fn handle_client(mut stream: TcpStream, config: &ServerConfig) {
+    let result = catch_unwind(AssertUnwindSafe(|| serve_client(&stream, config)))
+        .unwrap_or_else(|_| Err(io::Error::other(ServerError::new(ErrorCode::Internal, "Internal server error"))));
+
+    /*-----------REPORT ERROR TO CLIENT-----------*/
+    if let Err(error) = result {
+        let server_error = server_error(&error);
+        if server_error.is_some_and(|e| e.code == ErrorCode::Timeout) {
+            println!("Session timed out: {}", error);
+        } else {
+            println!("Session failed: {}", error);
+        }
+        if let Some(server_error) = server_error {
+            let _ = write_frame(&mut stream, &server_error.to_frame());
+            drain_and_close(&mut stream);
+        }
+    }
+    /*-----------REPORT ERROR TO CLIENT-----------*/
+
+    println!("Client disconnected");
+}
+
+// Closing with unread client data in the socket would reset the connection and could
+// discard the ERROR frame before the client reads it, so discard input until the client hangs up
+// or DRAIN_TIMEOUT has passed.
+fn drain_and_close(stream: &mut TcpStream) {
+    let _ = stream.shutdown(Shutdown::Write);
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    let mut buffer = [0u8; CHUNK_SIZE];
+    while let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|left| !left.is_zero()) {
+        let _ = stream.set_read_timeout(Some(left));
+        if !matches!(stream.read(&mut buffer), Ok(n) if n > 0) {
+            break;
+        }
+    }
+}
+
+// Runs the connection's sessions: one, or one per message in a batch. Errors wrapping
+// a `ServerError` are reported to the client by the caller.
+fn serve_client(stream: &TcpStream, config: &ServerConfig) -> io::Result<()> {
+    let rejected = |error: ServerError| io::Error::new(io::ErrorKind::InvalidData, error);
+    let mut waiting = Phase::Handshake; // the first HELLO is part of the handshake
+
+    loop {
+        let clock = config.timeouts.start();
+
+        /*-----------HANDSHAKE-----------*/
+        let request = match read_frame(&mut clock.timed(stream, waiting)) {
+            // a batch ends when the client hangs up instead of sending another HELLO
+            Err(e) if waiting == Phase::Idle && e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            request => request?,
+        };
+        let (mut session, reply) = Session::start(config, &request).map_err(rejected)?;
+        write_frame(&mut clock.timed(stream, Phase::Handshake), &reply)?;
+        /*-----------HANDSHAKE-----------*/
+
+        loop {
+            let frame = read_frame(&mut clock.timed(stream, Phase::Idle))?;
+            match session.on_frame(&frame).map_err(rejected)? {
+                Step::Continue(Some(reply)) => write_frame(&mut clock.timed(stream, Phase::Idle), &reply)?,
+                Step::Continue(None) => {}
+                Step::Finish(reply) => return write_frame(&mut clock.timed(stream, Phase::Idle), &reply),
+                Step::Next(reply) => {
+                    write_frame(&mut clock.timed(stream, Phase::Idle), &reply)?;
+                    break;
+                }
+            }
+        }
+        waiting = Phase::Idle; // the next HELLO may take as long as any frame between messages
+    }
+}
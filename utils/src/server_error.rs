@@ -0,0 +1,147 @@
+/*!
+ * -----------------------------------------
+ * Typed errors sent from server to client in ERROR frames.
+ * Each error carries a code naming its class, an optional
+ * handshake rejection reason, an optional symbol offset,
+ * and a human-readable message.
+ * Clients map each class to its own process exit code.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::error::Error;
+use std::fmt;
+use crate::frame::{parse_fields, Frame, FrameKind};
+use crate::handshake::{RejectReason, Rejection};
+use crate::EXIT_KEY_REUSE;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    BadHandshake,
+    InvalidSymbol,
+    KeyTooShort,
+    Oversized,
+    Protocol,
+    Internal,
+    KeyReuse,
+    UnknownKey,
+    Busy,
+    Timeout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    pub code: ErrorCode,
+    pub reason: Option<RejectReason>, // set for BadHandshake
+    pub offset: Option<u64>, // set for InvalidSymbol
+    pub message: String,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl ErrorCode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::BadHandshake => "bad-handshake",
+            ErrorCode::InvalidSymbol => "invalid-symbol",
+            ErrorCode::KeyTooShort => "key-too-short",
+            ErrorCode::Oversized => "oversized",
+            ErrorCode::Protocol => "protocol",
+            ErrorCode::Internal => "internal",
+            ErrorCode::KeyReuse => "key-reuse",
+            ErrorCode::UnknownKey => "unknown-key",
+            ErrorCode::Busy => "busy",
+            ErrorCode::Timeout => "timeout",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bad-handshake" => Some(ErrorCode::BadHandshake),
+            "invalid-symbol" => Some(ErrorCode::InvalidSymbol),
+            "key-too-short" => Some(ErrorCode::KeyTooShort),
+            "oversized" => Some(ErrorCode::Oversized),
+            "protocol" => Some(ErrorCode::Protocol),
+            "internal" => Some(ErrorCode::Internal),
+            "key-reuse" => Some(ErrorCode::KeyReuse),
+            "unknown-key" => Some(ErrorCode::UnknownKey),
+            "busy" => Some(ErrorCode::Busy),
+            "timeout" => Some(ErrorCode::Timeout),
+            _ => None,
+        }
+    }
+
+    /// Process exit code the clients use for this class of error.
+    /// 1 is reserved for usage and local file errors, 2 for I/O failures.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCode::BadHandshake => 3,
+            ErrorCode::InvalidSymbol => 4,
+            ErrorCode::KeyTooShort => 5,
+            ErrorCode::Oversized => 6,
+            ErrorCode::Protocol => 7,
+            ErrorCode::Internal => 8,
+            ErrorCode::KeyReuse => EXIT_KEY_REUSE,
+            ErrorCode::UnknownKey => 10,
+            ErrorCode::Busy => 11,
+            ErrorCode::Timeout => 12,
+        }
+    }
+}
+
+impl ServerError {
+    pub fn new(code: ErrorCode, message: &str) -> Self {
+        ServerError { code, reason: None, offset: None, message: message.to_string() }
+    }
+
+    pub fn invalid_symbol(offset: u64, message: &str) -> Self {
+        ServerError { offset: Some(offset), ..Self::new(ErrorCode::InvalidSymbol, message) }
+    }
+
+    pub fn to_frame(&self) -> Frame {
+        let mut payload = format!("code={}\n", self.code.name());
+        if let Some(reason) = self.reason {
+            payload.push_str(&format!("reason={}\n", reason.name()));
+        }
+        if let Some(offset) = self.offset {
+            payload.push_str(&format!("offset={}\n", offset));
+        }
+        payload.push_str(&format!("message={}\n", self.message.replace('\n', " ")));
+        Frame::new(FrameKind::Error, payload.into_bytes())
+    }
+
+    /// Decodes an ERROR frame. Payloads that can't be parsed become internal errors
+    /// carrying the raw text, so the client always has something to report.
+    pub fn from_frame(frame: &Frame) -> Self {
+        let fields = parse_fields(&frame.payload).unwrap_or_default();
+        let field = |name: &str| fields.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+        match field("code").and_then(ErrorCode::from_name) {
+            Some(code) => ServerError {
+                code,
+                reason: field("reason").and_then(RejectReason::from_name),
+                offset: field("offset").and_then(|v| v.parse().ok()),
+                message: field("message").unwrap_or("").to_string(),
+            },
+            None => Self::new(ErrorCode::Internal, &String::from_utf8_lossy(&frame.payload)),
+        }
+    }
+}
+
+impl From<Rejection> for ServerError {
+    fn from(rejection: Rejection) -> Self {
+        ServerError { reason: Some(rejection.reason), ..Self::new(ErrorCode::BadHandshake, &rejection.message) }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.reason, self.offset) {
+            (Some(reason), _) => write!(f, "{} ({})", self.message, reason.name()),
+            (None, Some(offset)) => write!(f, "{} at offset {} ({})", self.message, offset, self.code.name()),
+            (None, None) => write!(f, "{} ({})", self.message, self.code.name()),
+        }
+    }
+}
+
+impl Error for ServerError {}
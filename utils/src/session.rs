@@ -0,0 +1,112 @@
+/*!
+ * -----------------------------------------
+ * Server-side session state.
+ * Decides the answer to every frame a client sends, without
+ * doing any network I/O itself, so the threaded and async servers
+ * share one implementation of the protocol: the HELLO is
+ * answered with the negotiated session, DATA is run through
+ * the cipher, and END closes the session once the key has
+ * covered all of the data. A batch session then waits for
+ * the next message's HELLO instead of ending.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fs::File;
+use std::io::{Read, Take};
+use crate::cipher::OtpStream;
+use crate::error::OtpError;
+use crate::frame::{Frame, FrameKind};
+use crate::handshake::{negotiate, Hello, PadRef, RejectReason, Rejection, BATCH_FEATURE, SERVER_PAD_FEATURE};
+use crate::server::ServerConfig;
+use crate::server_error::{ErrorCode, ServerError};
+use crate::CHUNK_SIZE;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+pub(crate) struct Session {
+    otp: OtpStream,
+    pad: Option<Take<File>>, // key material from a server pad; DATA then carries only data
+    key_buffer: Vec<u8>, // the pad material for the current DATA frame
+    batch: bool, // another message may follow on the same connection
+    write_buffer: Vec<u8>,
+}
+
+/// What the server does after handling a frame.
+pub(crate) enum Step {
+    /// Send the frame, if any, and wait for the next one.
+    Continue(Option<Frame>),
+    /// Send the frame and end the session.
+    Finish(Frame),
+    /// Send the frame and wait for the next message's HELLO.
+    Next(Frame),
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl Session {
+    /// Answers the client's HELLO, returning the session and the reply to send.
+    /// With the server-pad feature, also opens the requested pad range.
+    pub(crate) fn start(config: &ServerConfig, request: &Frame) -> Result<(Session, Frame), ServerError> {
+        let features: &[&str] = if config.pads.is_some() { &[BATCH_FEATURE, SERVER_PAD_FEATURE] } else { &[BATCH_FEATURE] };
+        let request = Hello::from_frame(request)?;
+        let mut accepted = negotiate(&request, &config.operations, &config.mode, features)?;
+
+        /*-----------OPEN SERVER PAD-----------*/
+        let mut pad = None;
+        if let (Some(pads), true) = (&config.pads, accepted.has_feature(SERVER_PAD_FEATURE)) {
+            let requested = request.pad.as_ref()
+                .ok_or_else(|| Rejection::new(RejectReason::Malformed, "The server-pad feature needs a pad"))?;
+            let (offset, material) = pads.open(requested, accepted.operation, &accepted.mode)?;
+            accepted.pad = Some(PadRef { offset: Some(offset), ..requested.clone() });
+            pad = Some(material);
+        }
+        /*-----------OPEN SERVER PAD-----------*/
+
+        let session = Session {
+            otp: OtpStream::new(accepted.operation, accepted.mode.clone()),
+            pad,
+            key_buffer: Vec::new(),
+            batch: accepted.has_feature(BATCH_FEATURE),
+            write_buffer: Vec::with_capacity(CHUNK_SIZE),
+        };
+        Ok((session, accepted.to_frame()))
+    }
+
+    /// Handles one frame after the handshake.
+    pub(crate) fn on_frame(&mut self, frame: &Frame) -> Result<Step, ServerError> {
+        match frame.kind {
+            FrameKind::Data => {
+                /*-----------APPLY CIPHER-----------*/
+                self.write_buffer.clear();
+                let applied = match &mut self.pad {
+                    Some(pad) => {
+                        if frame.payload.len() as u64 > pad.limit() {
+                            return Err(ServerError::new(ErrorCode::KeyTooShort, "Data is longer than the pad range"));
+                        }
+                        self.key_buffer.resize(frame.payload.len(), 0);
+                        pad.read_exact(&mut self.key_buffer)
+                            .map_err(|e| ServerError::new(ErrorCode::Internal, &format!("Error reading pad: {}", e)))?;
+                        self.otp.apply(&frame.payload, &self.key_buffer, &mut self.write_buffer)
+                    }
+                    None => self.otp.update(&frame.payload, &mut self.write_buffer),
+                };
+                applied.map_err(|e| match e {
+                    OtpError::InvalidSymbol { position, message } => ServerError::invalid_symbol(position, &message),
+                    e => ServerError::new(ErrorCode::Internal, &e.to_string()),
+                })?;
+                /*-----------APPLY CIPHER-----------*/
+
+                Ok(Step::Continue((!self.write_buffer.is_empty()).then(|| Frame::data(&self.write_buffer))))
+            }
+            FrameKind::End => {
+                /*-----------CHECK KEY LENGTH-----------*/
+                if !self.otp.is_aligned() {
+                    return Err(ServerError::new(ErrorCode::KeyTooShort, "Key is shorter than the data"));
+                }
+                /*-----------CHECK KEY LENGTH-----------*/
+                Ok(if self.batch { Step::Next(Frame::end()) } else { Step::Finish(Frame::end()) })
+            }
+            _ => Err(ServerError::new(ErrorCode::Protocol, &format!("Unexpected {:?} frame", frame.kind))),
+        }
+    }
+}
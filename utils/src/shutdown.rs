@@ -0,0 +1,74 @@
+/*!
+ * -----------------------------------------
+ * Graceful shutdown for the servers.
+ * On SIGTERM or SIGINT a server stops accepting connections,
+ * turns away clients still waiting for a worker, and gives
+ * active sessions a grace period to finish before exiting
+ * with a summary of what was drained or aborted.
+ * A second signal during the grace period exits at once.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::flag;
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+pub(crate) const SHUTDOWN_MESSAGE: &str = "Server is shutting down, try again later";
+const FORCED_EXIT: i32 = 130; // 128 + SIGINT, as a shell reports an interrupted process
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+/// Set once SIGTERM or SIGINT has been received.
+#[derive(Clone)]
+pub(crate) struct ShutdownFlag(Arc<AtomicBool>);
+
+/// What happened to the sessions a server had when it shut down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainSummary {
+    pub drained: usize, // active sessions that finished within the grace period
+    pub aborted: usize, // active sessions still running when it ran out
+    pub turned_away: usize, // queued clients told the server was shutting down
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl ShutdownFlag {
+    /// Installs SIGTERM and SIGINT handlers. The first signal sets the flag;
+    /// a second one, once the flag is set, exits the process immediately.
+    pub(crate) fn register() -> io::Result<Self> {
+        let requested = Arc::new(AtomicBool::new(false));
+        for signal in [SIGTERM, SIGINT] {
+            flag::register_conditional_shutdown(signal, FORCED_EXIT, Arc::clone(&requested))?;
+            flag::register(signal, Arc::clone(&requested))?;
+        }
+        Ok(ShutdownFlag(requested))
+    }
+
+    pub(crate) fn requested(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl DrainSummary {
+    /// Summary for a drain that started with `active` sessions and ended with `remaining`.
+    pub(crate) fn new(active: usize, remaining: usize, turned_away: usize) -> Self {
+        DrainSummary { drained: active.saturating_sub(remaining), aborted: remaining, turned_away }
+    }
+}
+
+impl fmt::Display for DrainSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} sessions drained, {} aborted, {} queued clients turned away",
+            self.drained, self.aborted, self.turned_away
+        )
+    }
+}
@@ -0,0 +1,154 @@
+/*!
+ * -----------------------------------------
+ * Session timeouts for clients and servers.
+ * The handshake must finish within the handshake timeout,
+ * each later frame must arrive (or be sent) within the idle
+ * timeout, and the whole session within the session timeout.
+ * A peer that misses one gets a `timeout` error, so a
+ * client that goes silent can't hold a server worker forever.
+ * -----------------------------------------
+ */
+
+/*-----------USE STATEMENTS-----------*/
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use crate::args::Args;
+use crate::error::OtpError;
+use crate::server_error::{ErrorCode, ServerError};
+/*-----------USE STATEMENTS-----------*/
+
+/*-----------CONSTANT DEFINITIONS-----------*/
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/*-----------CONSTANT DEFINITIONS-----------*/
+
+/*-----------TYPE DEFINITIONS-----------*/
+/// Time limits for a session; `None` means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub handshake: Option<Duration>,
+    pub idle: Option<Duration>, // per frame after the handshake
+    pub session: Option<Duration>, // whole session, handshake included
+}
+
+/// Which timeout covers the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    Handshake,
+    Idle,
+}
+
+/// The timeouts of one session, measured from when it started.
+pub(crate) struct SessionClock {
+    timeouts: Timeouts,
+    started: Instant,
+}
+
+/// A stream whose reads and writes fail with a `timeout` error after a deadline.
+pub(crate) struct Timed<'a> {
+    stream: &'a TcpStream,
+    clock: &'a SessionClock,
+    phase: Phase,
+    deadline: Option<Instant>,
+}
+/*-----------TYPE DEFINITIONS-----------*/
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts { handshake: Some(DEFAULT_HANDSHAKE_TIMEOUT), idle: Some(DEFAULT_IDLE_TIMEOUT), session: None }
+    }
+}
+
+impl Timeouts {
+    /// Takes `--handshake-timeout`, `--idle-timeout` and `--session-timeout`, in seconds.
+    /// 0 turns a timeout off; options not given keep their defaults.
+    pub fn from_args(args: &mut Args) -> Result<Self, OtpError> {
+        let mut timeouts = Timeouts::default();
+        for (name, timeout) in [
+            ("--handshake-timeout", &mut timeouts.handshake),
+            ("--idle-timeout", &mut timeouts.idle),
+            ("--session-timeout", &mut timeouts.session),
+        ] {
+            if let Some(seconds) = args.parsed::<u64>(name)? {
+                *timeout = Some(Duration::from_secs(seconds)).filter(|t| !t.is_zero());
+            }
+        }
+        Ok(timeouts)
+    }
+
+    pub(crate) fn start(self) -> SessionClock {
+        SessionClock { timeouts: self, started: Instant::now() }
+    }
+}
+
+impl SessionClock {
+    /// When the next frame in `phase` must be done: the phase's timeout from now,
+    /// or the end of the session if that comes first.
+    pub(crate) fn deadline(&self, phase: Phase) -> Option<Instant> {
+        let limit = match phase {
+            Phase::Handshake => self.timeouts.handshake,
+            Phase::Idle => self.timeouts.idle,
+        };
+        let phase_end = limit.map(|limit| Instant::now() + limit);
+        let session_end = self.timeouts.session.map(|limit| self.started + limit);
+        match (phase_end, session_end) {
+            (Some(phase_end), Some(session_end)) => Some(phase_end.min(session_end)),
+            (phase_end, session_end) => phase_end.or(session_end),
+        }
+    }
+
+    /// The error for a frame that missed its deadline, naming the timeout that ran out.
+    pub(crate) fn expired(&self, phase: Phase) -> io::Error {
+        let message = match (self.timeouts.session, phase) {
+            (Some(limit), _) if self.started.elapsed() >= limit => format!("Session ran past the {}s session timeout", limit.as_secs()),
+            (_, Phase::Handshake) => format!("Handshake took longer than {}s", self.timeouts.handshake.unwrap_or_default().as_secs()),
+            (_, Phase::Idle) => format!("Peer was idle for {}s", self.timeouts.idle.unwrap_or_default().as_secs()),
+        };
+        io::Error::new(io::ErrorKind::TimedOut, ServerError::new(ErrorCode::Timeout, &message))
+    }
+
+    /// Wraps `stream` for the next frame in `phase`.
+    pub(crate) fn timed<'a>(&'a self, stream: &'a TcpStream, phase: Phase) -> Timed<'a> {
+        Timed { stream, clock: self, phase, deadline: self.deadline(phase) }
+    }
+}
+
+impl Timed<'_> {
+    // Time left before the deadline, as a socket timeout.
+    fn time_left(&self) -> io::Result<Option<Duration>> {
+        match self.deadline {
+            None => Ok(None),
+            Some(deadline) => deadline.checked_duration_since(Instant::now())
+                .filter(|left| !left.is_zero())
+                .map(Some)
+                .ok_or_else(|| self.clock.expired(self.phase)),
+        }
+    }
+
+    // Socket timeouts surface as WouldBlock on Unix and TimedOut on Windows.
+    fn check(&self, error: io::Error) -> io::Error {
+        match error.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => self.clock.expired(self.phase),
+            _ => error,
+        }
+    }
+}
+
+impl Read for Timed<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(self.time_left()?)?;
+        self.stream.read(buf).map_err(|e| self.check(e))
+    }
+}
+
+impl Write for Timed<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(self.time_left()?)?;
+        self.stream.write(buf).map_err(|e| self.check(e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}